@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+use lib::*;
+
+extern crate lib;
+
+fn main() -> isize {
+    let count = sys_list_app_info(&mut []);
+    if count == 0 {
+        println!("[!] No app found in list!");
+        return 0;
+    }
+
+    let mut apps = vec![AppInfo::default(); count];
+    let count = sys_list_app_info(&mut apps).min(apps.len());
+    apps.truncate(count);
+
+    apps.sort_by(|a, b| a.name().cmp(b.name()));
+
+    println!(" Name             |   Size   | Entry");
+    for app in apps.iter() {
+        println!(
+            " {:<16} | {:>6} K | {:#x}",
+            app.name(),
+            app.size / 1024,
+            app.entry
+        );
+    }
+
+    0
+}
+
+entry!(main);
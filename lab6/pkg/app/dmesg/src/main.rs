@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+use alloc::string::String;
+use lib::*;
+
+extern crate lib;
+
+fn main() -> isize {
+    print!("Options: ");
+
+    let input = lib::stdin().read_line();
+    let mut options = input.split_whitespace();
+
+    let permitted = match options.next() {
+        None => print_log(false),
+        Some("-c") => print_log(true),
+        Some("-C") => sys_syslog_clear(),
+        Some("-n") => {
+            let level = options.next().unwrap_or_default();
+            match LEVEL_NAMES.iter().position(|&name| name == level) {
+                Some(level) => sys_syslog_console_level(level),
+                None => {
+                    errln!("dmesg: invalid console level: {}", level);
+                    return 1;
+                }
+            }
+        }
+        Some("-l") => {
+            let spec = options.next().unwrap_or_default();
+            if !sys_syslog_level(spec) {
+                errln!("dmesg: invalid level or not permitted: {}", spec);
+                return 1;
+            }
+            true
+        }
+        Some(_) => {
+            print_usage();
+            return 1;
+        }
+    };
+
+    if !permitted {
+        errln!("dmesg: not allowed to change the kernel log");
+        return 1;
+    }
+
+    0
+}
+
+/// Print the kernel log, false if it may not be cleared
+fn print_log(clear: bool) -> bool {
+    // records may be added between the two calls
+    let mut buf = vec![0u8; sys_syslog_size() + 1024];
+    let Some(len) = sys_syslog_read(&mut buf, clear) else {
+        return false;
+    };
+
+    print!("{}", String::from_utf8_lossy(&buf[..len]));
+    true
+}
+
+fn print_usage() {
+    println!(
+        "Options:\n\
+        (none)              - print the kernel log\n\
+        -c                  - print and clear the kernel log\n\
+        -C                  - clear the kernel log\n\
+        -n <level>          - set the level printed to the console\n\
+        -l [module=]<level> - set the level kept in the log\n\
+        levels: {}",
+        LEVEL_NAMES.join(", ")
+    );
+}
+
+entry!(main);
@@ -16,6 +16,11 @@ fn main() -> isize {
     // which may cause unexpected behavior since we won't copy the heap in `fork`
     let pid = sys_fork();
 
+    if pid == u16::MAX {
+        errln!("Failed to fork: process limit reached");
+        return -1;
+    }
+
     if pid == 0 {
         println!("I am the child process");
 
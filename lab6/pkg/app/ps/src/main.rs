@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+use lib::*;
+
+extern crate lib;
+
+fn main() -> isize {
+    // the process count may change between the two calls
+    let count = sys_list_process(&mut []);
+    let mut procs = vec![ProcessInfo::default(); count + 4];
+    let count = sys_list_process(&mut procs).min(procs.len());
+    procs.truncate(count);
+
+    procs.sort_by_key(|p| p.pid);
+
+    println!("  PID | PPID | Process Name     |  Ticks  |  CPU%  |  Memory  | Status");
+    for p in procs.iter() {
+        // share of cpu since the process was created, in per mille
+        let cpu = (p.times.cpu() as u128 * 1000 / p.times.elapsed().max(1) as u128) as u64;
+        println!(
+            " #{:<3} | #{:<3} | {:<16} | {:>7} | {:>3}.{} | {:>6} K | {:?}",
+            p.pid,
+            p.ppid,
+            p.name(),
+            p.ticks,
+            cpu / 10,
+            cpu % 10,
+            p.memory / 1024,
+            p.status
+        );
+    }
+
+    let ready = procs
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Ready)
+        .count();
+    let blocked = procs
+        .iter()
+        .filter(|p| p.status == ProcessStatus::Blocked)
+        .count();
+
+    println!(
+        "Total: {}, ready: {}, blocked: {}",
+        procs.len(),
+        ready,
+        blocked
+    );
+
+    0
+}
+
+entry!(main);
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+use lib::*;
+
+extern crate lib;
+
+/// Run the apps given as arguments one by one, e.g. as init for automated runs
+///
+/// Exit with the count of failed apps.
+fn main() -> isize {
+    let mut buf = vec![0u8; sys_get_args(&mut [])];
+    let len = sys_get_args(&mut buf).min(buf.len());
+    let args = core::str::from_utf8(&buf[..len]).unwrap_or_default();
+
+    if args.is_empty() {
+        errln!("runner: no apps to run");
+        return 1;
+    }
+
+    let mut failed = 0;
+    for app in args.split_whitespace() {
+        if sys_terminating() {
+            println!("[runner] asked to terminate, skipping the rest");
+            break;
+        }
+
+        println!("[runner] RUN  {}", app);
+
+        let pid = sys_spawn(app);
+        if pid == 0 {
+            println!("[runner] FAIL {} (failed to spawn)", app);
+            failed += 1;
+            continue;
+        }
+
+        match sys_wait_pid(pid) {
+            0 => println!("[runner] PASS {}", app),
+            ret => {
+                match Signal::from_exit_code(ret) {
+                    Some(signal) => println!("[runner] FAIL {} (killed by {:?})", app, signal),
+                    None => println!("[runner] FAIL {} (exit code {})", app, ret),
+                }
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "[runner] {} passed, {} failed",
+        args.split_whitespace().count() - failed,
+        failed
+    );
+
+    failed as isize
+}
+
+entry!(main);
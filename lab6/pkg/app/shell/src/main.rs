@@ -9,10 +9,28 @@ fn main() -> isize {
     loop {
         print!("[>] ");
         let line = stdin().read_line();
+        if let Some(path) = line.trim().strip_prefix("cd ") {
+            if !sys_chdir(path.trim()) {
+                println!("[!] cd: invalid path: {}", path);
+            }
+            continue;
+        }
         match line.trim() {
             "exit" => break,
-            "app" => sys_list_app(),
-            "ps" => sys_stat(),
+            "cd" => {
+                sys_chdir("/");
+            }
+            "pwd" => {
+                let mut buf = [0u8; 256];
+                let len = sys_getcwd(&mut buf).min(buf.len());
+                println!("{}", core::str::from_utf8(&buf[..len]).unwrap_or("?"));
+            }
+            "app" => {
+                sys_wait_pid(sys_spawn("app"));
+            }
+            "ps" => {
+                sys_wait_pid(sys_spawn("ps"));
+            }
             "hello" => {
                 sys_wait_pid(sys_spawn("hello"));
             }
@@ -37,6 +55,22 @@ fn main() -> isize {
             "dinner" => {
                 sys_wait_pid(sys_spawn("dinner"));
             }
+            "strace" => {
+                sys_wait_pid(sys_spawn("strace"));
+            }
+            "dmesg" => {
+                sys_wait_pid(sys_spawn("dmesg"));
+            }
+            "reboot" => {
+                if reboot(RebootMode::Reboot) {
+                    break;
+                }
+            }
+            "poweroff" => {
+                if reboot(RebootMode::PowerOff) {
+                    break;
+                }
+            }
             "help" => {
                 print_help();
             }
@@ -47,6 +81,18 @@ fn main() -> isize {
 }
 
 entry!(main);
+
+/// Only init may reboot, it exits for the kernel to reset the machine
+fn reboot(mode: RebootMode) -> bool {
+    if sys_reboot(mode) {
+        println!("[+] {:?}...", mode);
+        true
+    } else {
+        println!("[!] {:?}: not permitted, the shell is not init", mode);
+        false
+    }
+}
+
 fn print_help() {
     println!(
         "22361058\n\
@@ -57,7 +103,13 @@ fn print_help() {
         hello           - 运行 hello world 应用程序\n\
         fac             - 运行阶乘计算应用程序\n\
         clear           - 清除屏幕\n\
+        cd <dir>        - 切换工作目录\n\
+        pwd             - 显示当前工作目录\n\
         fork            - 运行 fork 测试应用程序\n\
+        strace          - 跟踪应用程序的系统调用\n\
+        dmesg           - 查看内核日志\n\
+        reboot          - 重启系统\n\
+        poweroff        - 关闭系统\n\
         help            - 显示此帮助信息"
     );
 }
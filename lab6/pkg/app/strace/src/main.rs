@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+
+use lib::*;
+
+extern crate lib;
+
+const RECORD_BATCH: usize = 32;
+
+fn main() -> isize {
+    print!("Program: ");
+
+    let input = lib::stdin().read_line();
+    let name = input.trim();
+
+    let cpid = sys_get_pid();
+
+    // the trace flag is inherited by the spawned process
+    sys_trace(0, true);
+    let pid = sys_spawn(name);
+    sys_trace(0, false);
+
+    if pid == 0 {
+        errln!("strace: failed to spawn {}", name);
+        return 1;
+    }
+
+    let ret = sys_wait_pid(pid);
+
+    let mut records = [TraceRecord::default(); RECORD_BATCH];
+    loop {
+        let count = sys_read_trace(&mut records);
+        if count == 0 {
+            break;
+        }
+
+        for rec in records[..count].iter().filter(|r| r.pid != cpid) {
+            print_record(rec);
+        }
+    }
+
+    println!("+++ {}#{} exited with {} +++", name, pid, ret);
+
+    0
+}
+
+fn print_record(rec: &TraceRecord) {
+    let syscall = format!("{:?}", Syscall::from(rec.syscall));
+
+    print!(
+        "[{:>8}] #{:<3} {:<10}({:#x}, {:#x}, {:#x})",
+        rec.tick, rec.pid, syscall, rec.args[0], rec.args[1], rec.args[2]
+    );
+
+    if rec.returned {
+        println!(" = {}", rec.ret as isize);
+    } else {
+        println!(" = ?");
+    }
+}
+
+entry!(main);
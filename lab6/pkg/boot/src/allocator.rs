@@ -12,3 +12,16 @@ unsafe impl FrameAllocator<Size4KiB> for UEFIFrameAllocator {
         Some(frame)
     }
 }
+
+/// Copy data into new pages, which are left to the kernel
+pub fn copy_to_pages(data: &[u8]) -> &'static [u8] {
+    let pages = data.len() / 0x1000 + 1;
+    let mem_start =
+        uefi::boot::allocate_pages(AllocateType::AnyPages, MemoryType::LOADER_DATA, pages)
+            .expect("Failed to allocate pages");
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(data.as_ptr(), mem_start.as_ptr(), data.len());
+        core::slice::from_raw_parts(mem_start.as_ptr(), data.len())
+    }
+}
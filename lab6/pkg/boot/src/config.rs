@@ -1,7 +1,34 @@
+use arrayvec::ArrayVec;
 use core::str::FromStr;
 
-/// Config for the bootloader
+/// Maximum count of entries in the boot menu
+const MAX_ENTRIES: usize = 8;
+
+/// Seconds to wait in the boot menu if `timeout` is not set
+const DEFAULT_TIMEOUT: u64 = 5;
+
+/// Entries of the boot menu
+///
+/// Keys before the first `[name]` line apply to all entries, a file
+/// without sections has a single entry.
+#[derive(Debug)]
+pub struct BootMenu<'a> {
+    pub entries: ArrayVec<Entry<'a>, MAX_ENTRIES>,
+    /// Index of the entry booted when the timeout expires
+    pub default: usize,
+    /// Seconds to wait for a choice, 0 boots the default entry at once
+    pub timeout: u64,
+}
+
+/// A named set of boot options
 #[derive(Debug)]
+pub struct Entry<'a> {
+    pub name: &'a str,
+    pub config: Config<'a>,
+}
+
+/// Config for the bootloader
+#[derive(Clone, Copy, Debug)]
 pub struct Config<'a> {
     /// The address at which the kernel stack is placed
     pub kernel_stack_address: u64,
@@ -15,8 +42,16 @@ pub struct Config<'a> {
     pub kernel_path: &'a str,
     /// Kernel command line
     pub cmdline: &'a str,
+    /// The path of the initial ramdisk image, empty for none
+    pub initrd: &'a str,
+    /// SHA-256 digest of the kernel ELF in hex, empty for none
+    pub kernel_sha256: &'a str,
+    /// The path of a `sha256sum` style list of the kernel and apps, empty for none
+    pub checksums: &'a str,
     /// Load apps into memory, when no fs implemented in kernel
     pub load_apps: bool,
+    /// The resolution of the screen, `None` keeps the mode of the firmware
+    pub resolution: Option<(usize, usize)>,
 }
 
 const DEFAULT_CONFIG: Config = Config {
@@ -26,27 +61,78 @@ const DEFAULT_CONFIG: Config = Config {
     physical_memory_offset: 0xFFFF_8000_0000_0000,
     kernel_path: "\\KERNEL.ELF",
     cmdline: "",
+    initrd: "",
+    kernel_sha256: "",
+    checksums: "",
     load_apps: false,
+    resolution: None,
 };
 
-impl<'a> Config<'a> {
+impl<'a> BootMenu<'a> {
     pub fn parse(content: &'a [u8]) -> Self {
         let content = core::str::from_utf8(content).expect("failed to parse config as utf8");
-        let mut config = DEFAULT_CONFIG;
+        let mut global = DEFAULT_CONFIG;
+        let mut default = None;
+        let mut timeout = DEFAULT_TIMEOUT;
+        let mut entries = ArrayVec::<Entry, MAX_ENTRIES>::new();
+
         for line in content.lines() {
             let line = line.trim();
             // skip empty and comment
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
+            // start an entry with the global keys
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let entry = Entry {
+                    name: name.trim(),
+                    config: global,
+                };
+                if entries.try_push(entry).is_err() {
+                    warn!("too many entries, ignoring: {}", name);
+                    break;
+                }
+                continue;
+            }
             // parse 'key=value'
-            if let Some((key, value)) = line.split_once('=') {
-                config.process(key, value);
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match (entries.last_mut(), key) {
+                (Some(entry), _) => entry.config.process(key, value),
+                (None, "default") => default = Some(value),
+                (None, "timeout") => timeout = u64::from_str(value).unwrap_or(DEFAULT_TIMEOUT),
+                (None, _) => global.process(key, value),
             }
         }
-        config
+
+        if entries.is_empty() {
+            entries.push(Entry {
+                name: "default",
+                config: global,
+            });
+        }
+
+        let default = match default {
+            Some(name) => entries
+                .iter()
+                .position(|e| e.name == name)
+                .unwrap_or_else(|| {
+                    warn!("undefined default entry: {}", name);
+                    0
+                }),
+            None => 0,
+        };
+
+        Self {
+            entries,
+            default,
+            timeout,
+        }
     }
+}
 
+impl<'a> Config<'a> {
     fn process(&mut self, key: &str, value: &'a str) {
         info!("parse {} = {}", key, value);
         let r10 = u64::from_str(value).unwrap_or(0);
@@ -64,8 +150,18 @@ impl<'a> Config<'a> {
             "kernel_path" => self.kernel_path = value,
             "kernel_stack_auto_grow" => self.kernel_stack_auto_grow = r10,
             "cmdline" => self.cmdline = value,
+            "initrd" => self.initrd = value,
+            "kernel_sha256" => self.kernel_sha256 = value,
+            "checksums" => self.checksums = value,
             "load_apps" => self.load_apps = r10 != 0,
+            "resolution" => self.resolution = parse_resolution(value),
             _ => warn!("undefined config key: {}", key),
         }
     }
 }
+
+/// Parse a resolution like `1024x768`
+fn parse_resolution(value: &str) -> Option<(usize, usize)> {
+    let (width, height) = value.split_once('x')?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
@@ -21,7 +21,7 @@ pub fn open_file(path: &str) -> RegularFile {
 
     let handle = open_root()
         .open(cstr_path, FileMode::Read, FileAttribute::empty())
-        .expect("Failed to open file");
+        .unwrap_or_else(|e| panic!("Failed to open file {}: {:?}", path, e.status()));
 
     match handle.into_type().expect("Failed to into_type") {
         FileType::Regular(regular) => regular,
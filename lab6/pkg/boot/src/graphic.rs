@@ -0,0 +1,57 @@
+use uefi::boot::*;
+use uefi::proto::console::gop::{GraphicsOutput, PixelFormat};
+
+use crate::GraphicInfo;
+
+/// Set the graphic mode to `resolution` if available, and get the framebuffer
+pub fn init_graphic(resolution: Option<(usize, usize)>) -> Option<GraphicInfo> {
+    let handle = get_handle_for_protocol::<GraphicsOutput>().ok()?;
+
+    // the text console of the firmware is drawn with GOP as well,
+    // opening it exclusively would disconnect the console
+    let mut gop = unsafe {
+        open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle,
+                agent: image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+
+    if let Some((width, height)) = resolution {
+        let mode = gop
+            .modes()
+            .find(|mode| mode.info().resolution() == (width, height));
+
+        match mode {
+            Some(mode) => gop.set_mode(&mode).expect("Failed to set graphic mode"),
+            None => warn!("Graphic mode {}x{} is not available", width, height),
+        }
+    }
+
+    let mode = gop.current_mode_info();
+    let (width, height) = mode.resolution();
+
+    if mode.pixel_format() == PixelFormat::BltOnly {
+        warn!("Graphic mode {}x{} has no framebuffer", width, height);
+        return None;
+    }
+
+    let mut fb = gop.frame_buffer();
+    info!(
+        "Graphic mode: {}x{}, {:?}, framebuffer at {:#x}",
+        width,
+        height,
+        mode.pixel_format(),
+        fb.as_mut_ptr() as u64
+    );
+
+    Some(GraphicInfo {
+        mode,
+        fb_addr: fb.as_mut_ptr() as u64,
+        fb_size: fb.size() as u64,
+    })
+}
@@ -1,10 +1,10 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 pub use uefi::Status;
 pub use uefi::boot::{MemoryAttribute, MemoryDescriptor, MemoryType};
 pub use uefi::data_types::chars::*;
 pub use uefi::data_types::*;
-pub use uefi::proto::console::gop::{GraphicsOutput, ModeInfo};
+pub use uefi::proto::console::gop::{GraphicsOutput, ModeInfo, PixelFormat};
 
 use arrayvec::ArrayVec;
 use core::ptr::NonNull;
@@ -15,15 +15,34 @@ use x86_64::structures::paging::{OffsetPageTable, PageTable};
 pub mod allocator;
 pub mod config;
 pub mod fs;
+pub mod graphic;
+pub mod sha256;
 
 pub use allocator::*;
 pub use fs::*;
+pub use graphic::*;
 
 #[macro_use]
 extern crate log;
 
 pub type MemoryMap = ArrayVec<MemoryDescriptor, 256>;
 
+/// Maximum length of the kernel command line
+pub const CMDLINE_SIZE: usize = 256;
+
+pub type Cmdline = ArrayString<CMDLINE_SIZE>;
+
+/// Graphic output information
+#[derive(Debug, Clone, Copy)]
+pub struct GraphicInfo {
+    /// Graphic mode, with the resolution, stride and pixel format
+    pub mode: ModeInfo,
+    /// Physical address of the framebuffer
+    pub fb_addr: u64,
+    /// Size of the framebuffer in bytes
+    pub fb_size: u64,
+}
+
 /// This structure represents the information that the bootloader passes to the kernel.
 pub struct BootInfo {
     /// The memory map
@@ -36,6 +55,21 @@ pub struct BootInfo {
     pub system_table: NonNull<core::ffi::c_void>,
     // Loaded apps
     pub loaded_apps: Option<AppList>,
+
+    /// Symbol table of the kernel, for backtraces
+    pub kernel_symbols: Option<elf::SymbolTable<'static>>,
+
+    /// Kernel command line
+    pub cmdline: Cmdline,
+
+    /// Physical address and length of the initial ramdisk image
+    pub initrd: Option<(u64, u64)>,
+
+    /// The framebuffer, if the firmware provides one
+    pub graphic_info: Option<GraphicInfo>,
+
+    /// Physical address of the ACPI RSDP, from the UEFI configuration table
+    pub rsdp_addr: Option<u64>,
 }
 
 /// Get current page table from CR3
@@ -7,12 +7,16 @@ extern crate log;
 extern crate alloc;
 
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::vec;
 use uefi::mem::memory_map::MemoryMap;
+use uefi::table::cfg::{ACPI_GUID, ACPI2_GUID};
 use uefi::{Status, entry};
 use x86_64::registers::control::*;
 use ysos_boot::*;
 mod config;
+mod menu;
+mod verify;
 
 const CONFIG_PATH: &str = "\\EFI\\BOOT\\boot.conf";
 
@@ -26,8 +30,11 @@ fn efi_main() -> Status {
     // 1. Load config
     let mut file = fs::open_file(CONFIG_PATH);
     let buffer = fs::load_file(&mut file);
-    let config = config::Config::parse(&buffer);
-    info!("Config: {:#x?}", config);
+    let boot_menu = config::BootMenu::parse(&buffer);
+    let entry = &boot_menu.entries[menu::select(&boot_menu)];
+    let config = entry.config;
+    info!("Booting entry {}: {:#x?}", entry.name, config);
+    let checksums = verify::Checksums::load(&config);
 
     // 2. Load ELF files
 
@@ -37,6 +44,40 @@ fn efi_main() -> Status {
     let mut file = fs::open_file(kernel_path);
 
     let buffer = fs::load_file(&mut file);
+    if !checksums.verify(kernel_path, buffer) {
+        error!("Refusing to boot entry {}.", entry.name);
+        return Status::SECURITY_VIOLATION;
+    }
+
+    // verify all images before touching the page table
+    let apps = if config.load_apps {
+        info!("Loading apps...");
+        let apps = load_apps();
+        for app in apps.iter() {
+            let path = format!("\\APP\\{}", app.name);
+            if !checksums.verify(&path, app.elf.input) {
+                error!("Refusing to boot entry {}.", entry.name);
+                return Status::SECURITY_VIOLATION;
+            }
+        }
+        Some(apps)
+    } else {
+        info!("Skip loading apps");
+        None
+    };
+
+    let initrd = if config.initrd.is_empty() {
+        None
+    } else {
+        info!("Loading initrd from: {}", config.initrd);
+        let initrd: &'static [u8] = load_file(&mut open_file(config.initrd));
+        if !checksums.verify(config.initrd, initrd) {
+            error!("Refusing to boot entry {}.", entry.name);
+            return Status::SECURITY_VIOLATION;
+        }
+        Some((initrd.as_ptr() as u64, initrd.len() as u64))
+    };
+
     let elf = match xmas_elf::ElfFile::new(buffer) {
         Ok(elf_file) => {
             info!(
@@ -52,6 +93,9 @@ fn efi_main() -> Status {
 
     set_entry(elf.header.pt2.entry_point() as usize);
 
+    // set the graphic mode before the memory map, as it may allocate
+    let graphic_info = init_graphic(config.resolution);
+
     // 3. Load MemoryMap
     let mmap = uefi::boot::memory_map(MemoryType::LOADER_DATA).expect("Failed to get memory map");
 
@@ -60,7 +104,7 @@ fn efi_main() -> Status {
         .map(|m| m.phys_start + m.page_count * 0x1000)
         .max()
         .unwrap()
-        .max(0x1_0000_0000); // include IOAPIC MMIO area
+        .max(graphic_info.map_or(0, |info| info.fb_addr + info.fb_size));
 
     // 4. Map ELF segments, kernel stack and physical memory to virtual memory
     let mut page_table = current_page_table();
@@ -117,19 +161,36 @@ fn efi_main() -> Status {
         info!("Write protect restored");
     }
 
+    // keep the symbol table for backtraces, as the file is freed
+    let kernel_symbols = elf::SymbolTable::from_elf(&elf).map(|symbols| {
+        elf::SymbolTable::new(
+            copy_to_pages(symbols.symtab()),
+            copy_to_pages(symbols.strtab()),
+        )
+    });
+
     free_elf(elf);
 
     // 5. Pass system table to kernel
     let ptr = uefi::table::system_table_raw().expect("Failed to get system table");
     let system_table = ptr.cast::<core::ffi::c_void>();
-    //load apps
-    let apps = if config.load_apps {
-        info!("Loading apps...");
-        Some(load_apps())
-    } else {
-        info!("Skip loading apps");
-        None
-    };
+
+    // the kernel finds the APICs and CPUs in ACPI tables
+    let rsdp_addr = uefi::system::with_config_table(|entries| {
+        let find = |guid| entries.iter().find(|entry| entry.guid == guid);
+        find(ACPI2_GUID)
+            .or_else(|| find(ACPI_GUID))
+            .map(|entry| entry.address as u64)
+    });
+    info!("ACPI RSDP at {:#x?}", rsdp_addr);
+    let mut cmdline = Cmdline::new();
+    for c in config.cmdline.chars() {
+        if cmdline.try_push(c).is_err() {
+            warn!("Kernel command line is truncated to {} bytes", CMDLINE_SIZE);
+            break;
+        }
+    }
+
     // 6. Exit boot and jump to ELF entry
     info!("Exiting boot services...");
 
@@ -142,6 +203,11 @@ fn efi_main() -> Status {
         physical_memory_offset: config.physical_memory_offset,
         system_table,
         loaded_apps: apps,
+        kernel_symbols,
+        cmdline,
+        initrd,
+        graphic_info,
+        rsdp_addr,
     };
 
     // align stack to 8 bytes
@@ -0,0 +1,86 @@
+//! Boot menu on the UEFI console
+//!
+//! The entries are listed with their kernel, a number key boots an entry,
+//! up and down select one to boot with enter. The default entry is booted
+//! when the timeout expires, and any key stops the countdown.
+
+use uefi::proto::console::text::{Key, ScanCode};
+use uefi::{print, println};
+
+use crate::config::BootMenu;
+
+/// Interval to poll the keyboard, in microseconds
+const POLL_INTERVAL: usize = 10_000;
+
+const POLLS_PER_SEC: u64 = 1_000_000 / POLL_INTERVAL as u64;
+
+/// Let the user choose an entry, return its index
+pub fn select(menu: &BootMenu) -> usize {
+    let count = menu.entries.len();
+    if count <= 1 || menu.timeout == 0 {
+        return menu.default;
+    }
+
+    println!("Boot menu:");
+    for (i, entry) in menu.entries.iter().enumerate() {
+        println!("  {}. {} ({})", i + 1, entry.name, entry.config.kernel_path);
+    }
+    println!("Press a number, or up/down and enter to choose an entry.");
+
+    let width = menu.entries.iter().map(|e| e.name.len()).max().unwrap_or(0);
+    let mut selected = menu.default;
+    let mut remaining = Some(menu.timeout * POLLS_PER_SEC);
+
+    show(menu.entries[selected].name, width, remaining);
+    loop {
+        let key = uefi::system::with_stdin(|stdin| stdin.read_key());
+
+        let Ok(Some(key)) = key else {
+            match remaining {
+                Some(0) => break,
+                Some(polls) => {
+                    remaining = Some(polls - 1);
+                    if polls % POLLS_PER_SEC == 0 {
+                        show(menu.entries[selected].name, width, remaining);
+                    }
+                }
+                None => {}
+            }
+            uefi::boot::stall(POLL_INTERVAL);
+            continue;
+        };
+
+        remaining = None;
+        match key {
+            Key::Printable(c) if char::from(c) == '\r' => break,
+            Key::Printable(c) => {
+                if let Some(n) = char::from(c).to_digit(10)
+                    && (1..=count).contains(&(n as usize))
+                {
+                    selected = n as usize - 1;
+                    break;
+                }
+            }
+            Key::Special(ScanCode::UP) => selected = (selected + count - 1) % count,
+            Key::Special(ScanCode::DOWN) => selected = (selected + 1) % count,
+            Key::Special(_) => {}
+        }
+        show(menu.entries[selected].name, width, remaining);
+    }
+
+    show(menu.entries[selected].name, width, None);
+    println!();
+    selected
+}
+
+/// Rewrite the status line with the selected entry
+fn show(name: &str, width: usize, remaining: Option<u64>) {
+    match remaining {
+        Some(polls) => print!(
+            "\r> {:<width$} booting in {}s ",
+            name,
+            polls.div_ceil(POLLS_PER_SEC)
+        ),
+        None => print!("\r> {:<width$}                ", name),
+    }
+}
@@ -0,0 +1,104 @@
+//! Integrity checks of the kernel, app and initrd images
+//!
+//! The expected SHA-256 digests come from `kernel_sha256` of the boot
+//! entry, or from the `checksums` file written by `sha256sum`, e.g.
+//! `<digest>  KERNEL.ELF`, `<digest>  APP/hello` and `<digest>  INITRD.IMG`.
+//! Once checksums are configured, a kernel without a digest is refused,
+//! while apps and the initrd without one are loaded with a warning.
+
+use ysos_boot::sha256::{self, Hex};
+use ysos_boot::{load_file, open_file};
+
+use crate::config::Config;
+
+pub struct Checksums<'a> {
+    kernel_path: &'a str,
+    kernel_sha256: &'a str,
+    /// Content of the checksums file, empty for none
+    list: &'a str,
+}
+
+impl<'a> Checksums<'a> {
+    pub fn load(config: &Config<'a>) -> Self {
+        let list = if config.checksums.is_empty() {
+            ""
+        } else {
+            info!("Loading checksums from: {}", config.checksums);
+            let content: &'static [u8] = load_file(&mut open_file(config.checksums));
+            core::str::from_utf8(content).expect("failed to parse checksums as utf8")
+        };
+
+        Self {
+            kernel_path: config.kernel_path,
+            kernel_sha256: config.kernel_sha256,
+            list,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.kernel_sha256.is_empty() || !self.list.is_empty()
+    }
+
+    /// The digest of the image at `path` as written in the config
+    fn expected(&self, path: &str) -> Option<&'a str> {
+        if !self.kernel_sha256.is_empty() && same_path(path, self.kernel_path) {
+            return Some(self.kernel_sha256);
+        }
+
+        self.list.lines().find_map(|line| {
+            let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+            // `*` marks a file hashed in binary mode
+            let name = name.trim_start();
+            let name = name.strip_prefix('*').unwrap_or(name);
+            same_path(name, path).then_some(digest)
+        })
+    }
+
+    /// Check the image loaded from `path`, false if it must not be booted
+    pub fn verify(&self, path: &str, data: &[u8]) -> bool {
+        let Some(expected) = self.expected(path) else {
+            if self.enabled() && same_path(path, self.kernel_path) {
+                error!("No SHA-256 digest of the kernel {}", path);
+                return false;
+            }
+            if self.enabled() {
+                warn!("No SHA-256 digest of {}, not verified", path);
+            }
+            return true;
+        };
+
+        let Some(expected) = sha256::parse_hex(expected) else {
+            error!("Invalid SHA-256 digest of {}: {}", path, expected);
+            return false;
+        };
+
+        let actual = sha256::digest(data);
+        if actual != expected {
+            error!(
+                "SHA-256 mismatch of {}, the image is corrupted or stale\n  expected: {}\n  actual:   {}",
+                path,
+                Hex(&expected),
+                Hex(&actual)
+            );
+            return false;
+        }
+
+        info!("SHA-256 of {} verified", path);
+        true
+    }
+}
+
+/// Compare paths on the FAT filesystem, which ignores case
+fn same_path(a: &str, b: &str) -> bool {
+    normalize(a).eq(normalize(b))
+}
+
+/// Uppercase `path` without the leading separator, with `\` as separators
+fn normalize(path: &str) -> impl Iterator<Item = char> + '_ {
+    path.trim_start_matches(['\\', '/'])
+        .chars()
+        .map(|c| match c {
+            '/' => '\\',
+            c => c.to_ascii_uppercase(),
+        })
+}
@@ -0,0 +1,32 @@
+use x86_64::structures::paging::{Size4KiB, mapper::MapToError};
+
+/// Errors raised while loading an ELF file
+#[derive(Debug)]
+pub enum ElfLoadError {
+    /// Not a 64-bit little endian ELF file
+    InvalidClass,
+    /// Not built for x86_64
+    InvalidMachine,
+    /// Neither an executable nor a position-independent executable
+    InvalidType,
+    /// A program header is malformed
+    InvalidSegment(&'static str),
+    /// A segment overlaps the previous one at the address
+    OverlappingSegments(u64),
+    /// A user segment at the address reaches beyond the lower half
+    KernelSpaceSegment(u64),
+    /// A user segment at the address overlaps memory reserved by the kernel
+    ReservedSegment(u64),
+    /// The dynamic segment or a relocation table is malformed
+    InvalidRelocation(&'static str),
+    /// The relocation type is not supported
+    UnsupportedRelocation(u32),
+    /// Failed to map a segment
+    Map(MapToError<Size4KiB>),
+}
+
+impl From<MapToError<Size4KiB>> for ElfLoadError {
+    fn from(err: MapToError<Size4KiB>) -> Self {
+        Self::Map(err)
+    }
+}
@@ -1,14 +1,34 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate log;
 
+use core::ops::Range;
 use core::ptr::{copy_nonoverlapping, write_bytes};
 
 use x86_64::structures::paging::page::PageRange;
 use x86_64::structures::paging::{mapper::*, *};
-use x86_64::{PhysAddr, VirtAddr, align_up};
-use xmas_elf::{ElfFile, program};
+use x86_64::{PhysAddr, VirtAddr};
+use xmas_elf::{ElfFile, header, program};
+
+mod error;
+mod reloc;
+mod symbols;
+mod tls;
+
+#[cfg(test)]
+mod testing;
+
+pub use error::*;
+pub use reloc::*;
+pub use symbols::*;
+pub use tls::*;
+
+/// User segments must end below the canonical lower half
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+
+/// Maximum count of `PT_LOAD` segments in an ELF file
+const MAX_LOAD_SEGMENTS: usize = 32;
 
 /// Map physical memory
 ///
@@ -90,21 +110,48 @@ pub fn map_range(
 pub fn load_elf(
     elf: &ElfFile,
     physical_offset: u64,
-    page_table: &mut impl Mapper<Size4KiB>,
+    page_table: &mut (impl Mapper<Size4KiB> + Translate),
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+    user_access: bool,
+) -> Result<(), ElfLoadError> {
+    load_elf_at(
+        elf,
+        0,
+        &[],
+        physical_offset,
+        page_table,
+        frame_allocator,
+        user_access,
+    )
+}
+
+/// Load & Map ELF file at `base`
+///
+/// segments are mapped at `base + p_vaddr`, and the relocations of
+/// position-independent executables are applied
+pub fn load_elf_at(
+    elf: &ElfFile,
+    base: u64,
+    reserved: &[Range<u64>],
+    physical_offset: u64,
+    page_table: &mut (impl Mapper<Size4KiB> + Translate),
     frame_allocator: &mut impl FrameAllocator<Size4KiB>,
     user_access: bool,
-) -> Result<(), MapToError<Size4KiB>> {
+) -> Result<(), ElfLoadError> {
+    validate(elf, base, reserved, user_access)?;
+
     let file_buf = elf.input.as_ptr();
 
     info!("Loading ELF file... @ {:#x}", file_buf as u64);
 
     for segment in elf.program_iter() {
-        if segment.get_type().unwrap() != program::Type::Load {
+        if segment.get_type() != Ok(program::Type::Load) {
             continue;
         }
 
         load_segment(
             file_buf,
+            base,
             physical_offset,
             &segment,
             page_table,
@@ -112,27 +159,99 @@ pub fn load_elf(
             user_access,
         )?
     }
+
+    if is_pie(elf) {
+        let count = relocate(elf, base, physical_offset, page_table)?;
+        trace!("Applied {} relocations at base {:#x}", count, base);
+    }
+
     Ok(())
 }
 
-/// Load & Map ELF segment
+/// Check the header and the program headers of ELF file
 ///
-/// load segment to new frame and set page table
-fn load_segment(
-    file_buf: *const u8,
-    physical_offset: u64,
-    segment: &program::ProgramHeader,
-    page_table: &mut impl Mapper<Size4KiB>,
-    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+/// user segments must lie in the lower half after adding `base`, out of
+/// the `reserved` ranges where the kernel maps the stack, heap or TLS
+pub fn validate(
+    elf: &ElfFile,
+    base: u64,
+    reserved: &[Range<u64>],
     user_access: bool,
-) -> Result<(), MapToError<Size4KiB>> {
-    trace!("Loading & mapping segment: {:#x?}", segment);
+) -> Result<(), ElfLoadError> {
+    let pt1 = &elf.header.pt1;
+    if pt1.class() != header::Class::SixtyFour || pt1.data() != header::Data::LittleEndian {
+        return Err(ElfLoadError::InvalidClass);
+    }
 
-    let mem_size = segment.mem_size();
-    let file_size = segment.file_size();
-    let file_offset = segment.offset() & !0xfff;
-    let virt_start_addr = VirtAddr::new(segment.virtual_addr());
+    if elf.header.pt2.machine().as_machine() != header::Machine::X86_64 {
+        return Err(ElfLoadError::InvalidMachine);
+    }
+
+    match elf.header.pt2.type_().as_type() {
+        header::Type::Executable | header::Type::SharedObject => {}
+        _ => return Err(ElfLoadError::InvalidType),
+    }
+
+    // (start, end) of loaded segments
+    let mut loaded: [(u64, u64); MAX_LOAD_SEGMENTS] = [(0, 0); MAX_LOAD_SEGMENTS];
+    let mut count = 0;
+
+    for segment in elf.program_iter() {
+        let ty = segment.get_type().map_err(ElfLoadError::InvalidSegment)?;
+
+        if ty != program::Type::Load || segment.mem_size() == 0 {
+            continue;
+        }
+
+        if segment.file_size() > segment.mem_size() {
+            return Err(ElfLoadError::InvalidSegment(
+                "file size exceeds memory size",
+            ));
+        }
+
+        let in_file = segment
+            .offset()
+            .checked_add(segment.file_size())
+            .is_some_and(|end| end <= elf.input.len() as u64);
+        if !in_file {
+            return Err(ElfLoadError::InvalidSegment("segment data out of file"));
+        }
+
+        let start = base
+            .checked_add(segment.virtual_addr())
+            .ok_or(ElfLoadError::InvalidSegment("segment address overflow"))?;
+        let end = start
+            .checked_add(segment.mem_size())
+            .ok_or(ElfLoadError::InvalidSegment("segment address overflow"))?;
+
+        if user_access && end > USER_SPACE_END {
+            return Err(ElfLoadError::KernelSpaceSegment(start));
+        }
+
+        if user_access && reserved.iter().any(|r| start < r.end && r.start < end) {
+            return Err(ElfLoadError::ReservedSegment(start));
+        }
 
+        if let Some(&(_, prev_end)) = loaded[..count]
+            .iter()
+            .find(|&&(prev_start, prev_end)| start < prev_end && prev_start < end)
+        {
+            return Err(ElfLoadError::OverlappingSegments(prev_end.min(end)));
+        }
+
+        if count == MAX_LOAD_SEGMENTS {
+            return Err(ElfLoadError::InvalidSegment("too many segments"));
+        }
+
+        loaded[count] = (start, end);
+        count += 1;
+    }
+
+    Ok(())
+}
+
+/// Page table flags for the pages of a segment
+pub fn segment_flags(segment: &program::ProgramHeader, user_access: bool) -> PageTableFlags {
     let mut page_table_flags = PageTableFlags::PRESENT;
 
     // 设置页表标志位
@@ -148,76 +267,112 @@ fn load_segment(
         page_table_flags |= PageTableFlags::USER_ACCESSIBLE;
     }
 
-    trace!("Segment page table flag: {:?}", page_table_flags);
+    page_table_flags
+}
 
-    let start_page = Page::containing_address(virt_start_addr);
-    let end_page = Page::containing_address(virt_start_addr + file_size - 1u64);
-    let pages = Page::range_inclusive(start_page, end_page);
+/// Merge the flags of two segments sharing a page
+///
+/// the page is executable if either segment is
+pub fn merge_flags(a: PageTableFlags, b: PageTableFlags) -> PageTableFlags {
+    let mut merged = a | b;
+    if !a.contains(PageTableFlags::NO_EXECUTE) || !b.contains(PageTableFlags::NO_EXECUTE) {
+        merged.remove(PageTableFlags::NO_EXECUTE);
+    }
+    merged
+}
 
-    let data = unsafe { file_buf.add(file_offset as usize) };
-    for (idx, page) in pages.enumerate() {
-        let frame = frame_allocator
-            .allocate_frame()
-            .ok_or(MapToError::FrameAllocationFailed)?;
+/// Load & Map ELF segment
+///
+/// load segment to new frame and set page table, segments may start
+/// mid-page and share their first or last page with another segment
+fn load_segment(
+    file_buf: *const u8,
+    base: u64,
+    physical_offset: u64,
+    segment: &program::ProgramHeader,
+    page_table: &mut (impl Mapper<Size4KiB> + Translate),
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+    user_access: bool,
+) -> Result<(), ElfLoadError> {
+    trace!("Loading & mapping segment: {:#x?}", segment);
 
-        let offset = idx as u64 * page.size();
-        let count = if file_size - offset < page.size() {
-            file_size - offset
-        } else {
-            page.size()
-        };
+    let mem_size = segment.mem_size();
+    let file_size = segment.file_size();
+    let file_offset = segment.offset();
+    let virt_start = base + segment.virtual_addr();
 
-        unsafe {
-            copy_nonoverlapping(
-                data.add(idx * page.size() as usize),
-                (frame.start_address().as_u64() + physical_offset) as *mut u8,
-                count as usize,
-            );
+    if mem_size == 0 {
+        return Ok(());
+    }
 
-            page_table
-                .map_to(page, frame, page_table_flags, frame_allocator)?
-                .flush();
+    let page_table_flags = segment_flags(segment, user_access);
+
+    trace!("Segment page table flag: {:?}", page_table_flags);
+
+    let start_page = Page::<Size4KiB>::containing_address(VirtAddr::new(virt_start));
+    let end_page = Page::containing_address(VirtAddr::new(virt_start + mem_size - 1));
+    let file_end = virt_start + file_size;
+
+    for page in Page::range_inclusive(start_page, end_page) {
+        let frame = match page_table.translate(page.start_address()) {
+            TranslateResult::Mapped {
+                frame: MappedFrame::Size4KiB(frame),
+                flags,
+                ..
+            } => {
+                // the page is shared with the previous segment
+                let merged = merge_flags(flags, page_table_flags);
 
-            if count < page.size() {
-                // zero the rest of the page
                 trace!(
-                    "Zeroing rest of the page: {:#x}",
-                    page.start_address().as_u64()
-                );
-                write_bytes(
-                    (frame.start_address().as_u64() + physical_offset + count) as *mut u8,
-                    0,
-                    (page.size() - count) as usize,
+                    "Merging page {:#x} flags: {:?}",
+                    page.start_address().as_u64(),
+                    merged
                 );
+
+                unsafe {
+                    page_table
+                        .update_flags(page, merged)
+                        .map_err(|_| ElfLoadError::InvalidSegment("cannot update page flags"))?
+                        .flush();
+                }
+
+                frame
             }
-        }
-    }
+            TranslateResult::NotMapped => {
+                let frame = frame_allocator
+                    .allocate_frame()
+                    .ok_or(MapToError::FrameAllocationFailed)?;
 
-    if mem_size > file_size {
-        // .bss section (or similar), which needs to be zeroed
-        let zero_start = virt_start_addr + file_size;
-        let zero_end = virt_start_addr + mem_size;
+                unsafe {
+                    // zero the whole page, which covers .bss (or similar)
+                    write_bytes(
+                        (frame.start_address().as_u64() + physical_offset) as *mut u8,
+                        0,
+                        page.size() as usize,
+                    );
 
-        // Map additional frames.
-        let start_address = VirtAddr::new(align_up(zero_start.as_u64(), Size4KiB::SIZE));
-        let start_page: Page = Page::containing_address(start_address);
-        let end_page = Page::containing_address(zero_end);
+                    page_table
+                        .map_to(page, frame, page_table_flags, frame_allocator)?
+                        .flush();
+                }
 
-        for page in Page::range_inclusive(start_page, end_page) {
-            let frame = frame_allocator
-                .allocate_frame()
-                .ok_or(MapToError::FrameAllocationFailed)?;
+                frame
+            }
+            _ => return Err(ElfLoadError::InvalidSegment("page mapped as huge page")),
+        };
+
+        // copy the part of file data that lies in this page
+        let page_start = page.start_address().as_u64();
+        let copy_start = page_start.max(virt_start);
+        let copy_end = (page_start + page.size()).min(file_end);
 
+        if copy_start < copy_end {
             unsafe {
-                page_table
-                    .map_to(page, frame, page_table_flags, frame_allocator)?
-                    .flush();
-
-                // zero bss section
-                write_bytes(
-                    (frame.start_address().as_u64() + physical_offset) as *mut u8,
-                    0,
-                    page.size() as usize,
+                copy_nonoverlapping(
+                    file_buf.add((file_offset + copy_start - virt_start) as usize),
+                    (frame.start_address().as_u64() + physical_offset + copy_start - page_start)
+                        as *mut u8,
+                    (copy_end - copy_start) as usize,
                 );
             }
         }
@@ -225,3 +380,165 @@ fn load_segment(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const TEXT: u64 = 0x40_0000;
+
+    /// An executable with segments `(flags, vaddr, filesz, memsz)`, the
+    /// file data of each is filled with its index plus 1
+    fn exec(segments: &[(u32, u64, u64, u64)]) -> Image {
+        let mut image = Image::new(ET_EXEC);
+        let mut offset = DATA_OFFSET;
+
+        for (i, &(flags, vaddr, filesz, memsz)) in segments.iter().enumerate() {
+            image.write(offset as usize, &vec![i as u8 + 1; filesz as usize]);
+            image.segment(PT_LOAD, flags, offset, vaddr, filesz, memsz);
+            offset += filesz;
+        }
+        image
+    }
+
+    fn check(image: &Image, base: u64, reserved: &[Range<u64>]) -> Result<(), ElfLoadError> {
+        validate(&ElfFile::new(image.bytes()).unwrap(), base, reserved, true)
+    }
+
+    #[test]
+    fn test_validate() {
+        let image = exec(&[
+            (PF_R | PF_X, TEXT, 0x100, 0x100),
+            (PF_R | PF_W, TEXT + 0x100, 0x80, 0x2000),
+        ]);
+        assert!(check(&image, 0, &[]).is_ok());
+
+        // the type and machine are checked
+        let mut image = exec(&[(PF_R, TEXT, 0x10, 0x10)]);
+        image.write(16, &1u16.to_le_bytes());
+        assert!(matches!(
+            check(&image, 0, &[]),
+            Err(ElfLoadError::InvalidType)
+        ));
+        image.write(16, &ET_EXEC.to_le_bytes());
+        image.write(18, &3u16.to_le_bytes());
+        assert!(matches!(
+            check(&image, 0, &[]),
+            Err(ElfLoadError::InvalidMachine)
+        ));
+    }
+
+    #[test]
+    fn test_validate_segments() {
+        let image = exec(&[(PF_R, TEXT, 0x200, 0x100)]);
+        assert!(matches!(
+            check(&image, 0, &[]),
+            Err(ElfLoadError::InvalidSegment(
+                "file size exceeds memory size"
+            ))
+        ));
+
+        let mut image = exec(&[(PF_R, TEXT, 0x100, 0x100)]);
+        image.segment(PT_LOAD, PF_R, 0x10_0000, TEXT + 0x1000, 0x10, 0x10);
+        assert!(matches!(
+            check(&image, 0, &[]),
+            Err(ElfLoadError::InvalidSegment("segment data out of file"))
+        ));
+
+        let image = exec(&[(PF_R, TEXT, 0x100, 0x200), (PF_R, TEXT + 0x100, 0x10, 0x10)]);
+        assert!(matches!(
+            check(&image, 0, &[]),
+            Err(ElfLoadError::OverlappingSegments(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_kernel_space() {
+        let image = exec(&[(PF_R, USER_SPACE_END - 0x1000, 0x10, 0x2000)]);
+        assert!(matches!(
+            check(&image, 0, &[]),
+            Err(ElfLoadError::KernelSpaceSegment(_))
+        ));
+
+        // the kernel itself is loaded in the higher half
+        let elf_image = ElfFile::new(image.bytes()).unwrap();
+        assert!(validate(&elf_image, 0, &[], false).is_ok());
+
+        // so is a user segment moved there by the base
+        let image = exec(&[(PF_R, TEXT, 0x10, 0x10)]);
+        assert!(matches!(
+            check(&image, USER_SPACE_END, &[]),
+            Err(ElfLoadError::KernelSpaceSegment(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_reserved() {
+        let reserved = [0x1000_0000..0x2000_0000, 0x4000_0000..0x5000_0000];
+
+        let image = exec(&[(PF_R | PF_W, 0x1fff_f000, 0x10, 0x2000)]);
+        assert!(matches!(
+            check(&image, 0, &reserved),
+            Err(ElfLoadError::ReservedSegment(0x1fff_f000))
+        ));
+
+        // the range is checked after adding the base
+        let image = exec(&[(PF_R, 0x10_0000, 0x10, 0x10)]);
+        assert!(check(&image, 0, &reserved).is_ok());
+        assert!(matches!(
+            check(&image, 0x4000_0000, &reserved),
+            Err(ElfLoadError::ReservedSegment(0x4010_0000))
+        ));
+
+        // segments right next to a reserved range are fine
+        let image = exec(&[
+            (PF_R, 0x0fff_f000, 0x10, 0x1000),
+            (PF_R, 0x2000_0000, 0x10, 0x1000),
+        ]);
+        assert!(check(&image, 0, &reserved).is_ok());
+    }
+
+    #[test]
+    fn test_merge_flags() {
+        let text = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        let data = text | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE;
+        let rodata = text | PageTableFlags::NO_EXECUTE;
+
+        assert_eq!(merge_flags(text, data), text | PageTableFlags::WRITABLE);
+        assert_eq!(merge_flags(data, text), text | PageTableFlags::WRITABLE);
+        assert_eq!(merge_flags(rodata, data), data);
+        assert_eq!(merge_flags(rodata, rodata), rodata);
+    }
+
+    #[test]
+    fn test_merge_shared_page() {
+        // text and data share the page at TEXT
+        let image = exec(&[
+            (PF_R | PF_X, TEXT + 0x200, 0x100, 0x100),
+            (PF_R | PF_W, TEXT + 0x300, 0x100, 0x1000),
+        ]);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let flags: Vec<_> = elf
+            .program_iter()
+            .map(|segment| segment_flags(&segment, true))
+            .collect();
+
+        let user = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        assert_eq!(flags[0], user);
+        assert_eq!(
+            flags[1],
+            user | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE
+        );
+
+        // the shared page is writable and executable, whichever is loaded first
+        assert_eq!(
+            merge_flags(flags[0], flags[1]),
+            user | PageTableFlags::WRITABLE
+        );
+        assert_eq!(
+            merge_flags(flags[1], flags[0]),
+            user | PageTableFlags::WRITABLE
+        );
+    }
+}
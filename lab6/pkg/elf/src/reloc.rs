@@ -0,0 +1,356 @@
+use core::ptr::copy_nonoverlapping;
+
+use x86_64::VirtAddr;
+use x86_64::structures::paging::{PageSize, Size4KiB, Translate};
+use xmas_elf::dynamic::Tag;
+use xmas_elf::program::{self, SegmentData};
+use xmas_elf::{ElfFile, header};
+
+use crate::ElfLoadError;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_GLOB_DAT: u32 = 6;
+const R_X86_64_JUMP_SLOT: u32 = 7;
+const R_X86_64_RELATIVE: u32 = 8;
+
+/// Size of `Elf64_Rela`
+const RELA_SIZE: usize = 24;
+/// Size of `Elf64_Sym`
+const SYM_SIZE: u64 = 24;
+
+/// Check if the ELF file is a position-independent executable
+pub fn is_pie(elf: &ElfFile) -> bool {
+    elf.header.pt2.type_().as_type() == header::Type::SharedObject
+}
+
+/// Apply the relocations listed in `PT_DYNAMIC` of an ELF loaded at `base`
+///
+/// The segments must be mapped in `page_table` already. Symbols are
+/// resolved against the file's own symbol table, as there is no dynamic
+/// linker. Return the count of applied relocations.
+pub fn relocate(
+    elf: &ElfFile,
+    base: u64,
+    physical_offset: u64,
+    page_table: &impl Translate,
+) -> Result<usize, ElfLoadError> {
+    let Some(dynamic) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(program::Type::Dynamic))
+    else {
+        return Ok(0);
+    };
+
+    let entries = match dynamic.get_data(elf) {
+        Ok(SegmentData::Dynamic64(entries)) => entries,
+        _ => return Err(ElfLoadError::InvalidRelocation("invalid dynamic segment")),
+    };
+
+    // (address, size) of DT_RELA and DT_JMPREL
+    let mut rela = (0, 0);
+    let mut jmprel = (0, 0);
+    let mut symtab = None;
+
+    for entry in entries {
+        match entry.get_tag() {
+            Ok(Tag::Null) => break,
+            Ok(Tag::Rela) => rela.0 = entry.get_ptr().unwrap_or(0),
+            Ok(Tag::RelaSize) => rela.1 = entry.get_val().unwrap_or(0),
+            Ok(Tag::JmpRel) => jmprel.0 = entry.get_ptr().unwrap_or(0),
+            Ok(Tag::PltRelSize) => jmprel.1 = entry.get_val().unwrap_or(0),
+            Ok(Tag::SymTab) => symtab = entry.get_ptr().ok(),
+            _ => {}
+        }
+    }
+
+    let mut count = 0;
+
+    for (addr, size) in [rela, jmprel] {
+        if size == 0 {
+            continue;
+        }
+
+        let table = file_data(elf, addr, size).ok_or(ElfLoadError::InvalidRelocation(
+            "relocation table out of file",
+        ))?;
+
+        for rela in table.chunks_exact(RELA_SIZE) {
+            let offset = read_u64(rela, 0);
+            let info = read_u64(rela, 8);
+            let addend = read_u64(rela, 16);
+
+            let value = match info as u32 {
+                R_X86_64_NONE => continue,
+                R_X86_64_RELATIVE => base.wrapping_add(addend),
+                ty @ (R_X86_64_64 | R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT) => {
+                    let sym = symbol_value(elf, symtab, info >> 32)
+                        .ok_or(ElfLoadError::InvalidRelocation("undefined symbol"))?;
+
+                    let value = base.wrapping_add(sym);
+                    if ty == R_X86_64_64 {
+                        value.wrapping_add(addend)
+                    } else {
+                        value
+                    }
+                }
+                ty => return Err(ElfLoadError::UnsupportedRelocation(ty)),
+            };
+
+            write_u64(
+                base.wrapping_add(offset),
+                value,
+                physical_offset,
+                page_table,
+            )?;
+
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Write `value` at `vaddr` through the physical memory mapping
+///
+/// A relocation target may straddle two pages backed by unrelated
+/// frames, so each page is translated and written on its own.
+fn write_u64(
+    vaddr: u64,
+    value: u64,
+    physical_offset: u64,
+    page_table: &impl Translate,
+) -> Result<(), ElfLoadError> {
+    let bytes = value.to_le_bytes();
+    let mut written = 0;
+
+    while written < bytes.len() {
+        let addr = vaddr
+            .checked_add(written as u64)
+            .and_then(|addr| VirtAddr::try_new(addr).ok())
+            .ok_or(ElfLoadError::InvalidRelocation(
+                "relocation target overflow",
+            ))?;
+
+        let target = page_table
+            .translate_addr(addr)
+            .ok_or(ElfLoadError::InvalidRelocation(
+                "relocation target not mapped",
+            ))?;
+
+        let in_page = (Size4KiB::SIZE - u64::from(addr.page_offset())) as usize;
+        let len = in_page.min(bytes.len() - written);
+
+        unsafe {
+            copy_nonoverlapping(
+                bytes[written..].as_ptr(),
+                (target.as_u64() + physical_offset) as *mut u8,
+                len,
+            );
+        }
+
+        written += len;
+    }
+
+    Ok(())
+}
+
+/// Get the file data of `size` bytes loaded at `vaddr`
+fn file_data<'a>(elf: &ElfFile<'a>, vaddr: u64, size: u64) -> Option<&'a [u8]> {
+    let segment = elf.program_iter().find(|ph| {
+        ph.get_type() == Ok(program::Type::Load)
+            && vaddr >= ph.virtual_addr()
+            && vaddr
+                .checked_add(size)
+                .is_some_and(|end| end <= ph.virtual_addr() + ph.file_size())
+    })?;
+
+    let start = (segment.offset() + vaddr - segment.virtual_addr()) as usize;
+    elf.input.get(start..start.checked_add(size as usize)?)
+}
+
+/// Get the value of a defined symbol in the dynamic symbol table
+fn symbol_value(elf: &ElfFile, symtab: Option<u64>, index: u64) -> Option<u64> {
+    let addr = symtab?.checked_add(index.checked_mul(SYM_SIZE)?)?;
+    let sym = file_data(elf, addr, SYM_SIZE)?;
+
+    // st_shndx is 0 for undefined symbols
+    let shndx = u16::from_le_bytes([sym[6], sym[7]]);
+    if shndx == 0 {
+        return None;
+    }
+
+    Some(read_u64(sym, 8))
+}
+
+#[inline]
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const BASE: u64 = 0x10_0000;
+
+    const DT_NULL: u64 = 0;
+    const DT_RELA: u64 = 7;
+    const DT_RELASZ: u64 = 8;
+    const DT_SYMTAB: u64 = 6;
+
+    const DYNAMIC_AT: u64 = DATA_OFFSET;
+    const RELA_AT: u64 = 0x240;
+    const SYMTAB_AT: u64 = 0x400;
+
+    /// A PIE with relocations `(offset, info, addend)` and the symbols
+    /// `(shndx, value)` from index 1, loaded in three pages
+    fn pie(relas: &[(u64, u64, u64)], symbols: &[(u16, u64)]) -> Image {
+        let mut image = Image::new(ET_DYN);
+
+        #[rustfmt::skip]
+        image.write_u64s(DYNAMIC_AT as usize, &[
+            DT_RELA, RELA_AT,
+            DT_RELASZ, (relas.len() * RELA_SIZE) as u64,
+            DT_SYMTAB, SYMTAB_AT,
+            DT_NULL, 0,
+        ]);
+
+        for (i, &(offset, info, addend)) in relas.iter().enumerate() {
+            image.write_u64s(RELA_AT as usize + i * RELA_SIZE, &[offset, info, addend]);
+        }
+
+        for (i, &(shndx, value)) in symbols.iter().enumerate() {
+            let at = (SYMTAB_AT + (i as u64 + 1) * SYM_SIZE) as usize;
+            image.write(at + 6, &shndx.to_le_bytes());
+            image.write_u64s(at + 8, &[value, 0]);
+        }
+
+        let len = image.bytes().len() as u64;
+        image.segment(PT_LOAD, PF_R | PF_W, 0, 0, len, 3 * PAGE_SIZE);
+        image.segment(PT_DYNAMIC, PF_R | PF_W, DYNAMIC_AT, DYNAMIC_AT, 0x40, 0x40);
+        image
+    }
+
+    fn read(page_table: &FakePageTable, vaddr: u64) -> u64 {
+        let offset = (vaddr % PAGE_SIZE) as usize;
+        read_u64(page_table.frame(vaddr), offset)
+    }
+
+    #[test]
+    fn test_is_pie() {
+        let image = Image::new(ET_DYN);
+        assert!(is_pie(&ElfFile::new(image.bytes()).unwrap()));
+
+        let image = Image::new(ET_EXEC);
+        assert!(!is_pie(&ElfFile::new(image.bytes()).unwrap()));
+    }
+
+    #[test]
+    fn test_relocate_relative() {
+        let image = pie(&[(0x1000, R_X86_64_RELATIVE as u64, 0x10)], &[]);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let page_table = FakePageTable::new(BASE, 3);
+
+        assert_eq!(relocate(&elf, BASE, 0, &page_table).unwrap(), 1);
+        assert_eq!(read(&page_table, BASE + 0x1000), BASE + 0x10);
+    }
+
+    #[test]
+    fn test_relocate_symbols() {
+        let image = pie(
+            &[
+                (0x1008, 1 << 32 | R_X86_64_64 as u64, 4),
+                (0x1010, 2 << 32 | R_X86_64_JUMP_SLOT as u64, 4),
+                (0x1018, R_X86_64_NONE as u64, 0),
+            ],
+            &[(1, 0x2000), (1, 0x2100)],
+        );
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let page_table = FakePageTable::new(BASE, 3);
+
+        assert_eq!(relocate(&elf, BASE, 0, &page_table).unwrap(), 2);
+        assert_eq!(read(&page_table, BASE + 0x1008), BASE + 0x2004);
+        // the addend only applies to R_X86_64_64
+        assert_eq!(read(&page_table, BASE + 0x1010), BASE + 0x2100);
+        assert_eq!(read(&page_table, BASE + 0x1018), 0);
+    }
+
+    #[test]
+    fn test_relocate_undefined_symbol() {
+        let image = pie(
+            &[(0x1000, 1 << 32 | R_X86_64_GLOB_DAT as u64, 0)],
+            &[(0, 0)],
+        );
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let page_table = FakePageTable::new(BASE, 3);
+
+        assert!(matches!(
+            relocate(&elf, BASE, 0, &page_table),
+            Err(ElfLoadError::InvalidRelocation("undefined symbol"))
+        ));
+    }
+
+    #[test]
+    fn test_relocate_across_pages() {
+        let addend = 0x1122_3344_5566_7788;
+        let image = pie(&[(0x1ffc, R_X86_64_RELATIVE as u64, addend)], &[]);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let page_table = FakePageTable::new(BASE, 3);
+
+        assert_eq!(relocate(&elf, BASE, 0, &page_table).unwrap(), 1);
+
+        let value = (BASE + addend).to_le_bytes();
+        assert_eq!(&page_table.frame(BASE + 0x1000)[0xffc..], &value[..4]);
+        assert_eq!(&page_table.frame(BASE + 0x2000)[..4], &value[4..]);
+    }
+
+    #[test]
+    fn test_relocate_unmapped() {
+        let page_table = FakePageTable::new(BASE, 1);
+
+        let image = pie(&[(0x1000, R_X86_64_RELATIVE as u64, 0)], &[]);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        assert!(matches!(
+            relocate(&elf, BASE, 0, &page_table),
+            Err(ElfLoadError::InvalidRelocation(
+                "relocation target not mapped"
+            ))
+        ));
+
+        // the second half lies in the page which is not mapped
+        let image = pie(&[(0xffc, R_X86_64_RELATIVE as u64, 0)], &[]);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        assert!(matches!(
+            relocate(&elf, BASE, 0, &page_table),
+            Err(ElfLoadError::InvalidRelocation(
+                "relocation target not mapped"
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_relocate_unsupported() {
+        let image = pie(&[(0x1000, 37, 0)], &[]);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let page_table = FakePageTable::new(BASE, 3);
+
+        assert!(matches!(
+            relocate(&elf, BASE, 0, &page_table),
+            Err(ElfLoadError::UnsupportedRelocation(37))
+        ));
+    }
+
+    #[test]
+    fn test_relocate_without_dynamic() {
+        let mut image = Image::new(ET_DYN);
+        image.segment(PT_LOAD, PF_R | PF_X, 0, 0, DATA_OFFSET, PAGE_SIZE);
+        let elf = ElfFile::new(image.bytes()).unwrap();
+        let page_table = FakePageTable::new(BASE, 1);
+
+        assert_eq!(relocate(&elf, BASE, 0, &page_table).unwrap(), 0);
+    }
+}
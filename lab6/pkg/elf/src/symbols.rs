@@ -0,0 +1,76 @@
+use xmas_elf::ElfFile;
+use xmas_elf::sections::ShType;
+
+/// Size of `Elf64_Sym`
+const SYM_SIZE: usize = 24;
+
+/// Symbol type of functions
+const STT_FUNC: u8 = 2;
+
+/// Function symbols of an ELF file, kept as raw `.symtab` and `.strtab`
+#[derive(Clone, Copy, Debug)]
+pub struct SymbolTable<'a> {
+    symtab: &'a [u8],
+    strtab: &'a [u8],
+}
+
+impl<'a> SymbolTable<'a> {
+    pub fn new(symtab: &'a [u8], strtab: &'a [u8]) -> Self {
+        Self { symtab, strtab }
+    }
+
+    /// Find `.symtab` and its string table, missing in stripped files
+    pub fn from_elf(elf: &ElfFile<'a>) -> Option<Self> {
+        let symtab = elf
+            .section_iter()
+            .find(|sh| sh.get_type() == Ok(ShType::SymTab))?;
+        let strtab = elf.section_header(symtab.link() as u16).ok()?;
+
+        let data = |offset: u64, size: u64| {
+            let start = usize::try_from(offset).ok()?;
+            let end = start.checked_add(usize::try_from(size).ok()?)?;
+            elf.input.get(start..end)
+        };
+
+        Some(Self {
+            symtab: data(symtab.offset(), symtab.size())?,
+            strtab: data(strtab.offset(), strtab.size())?,
+        })
+    }
+
+    pub fn symtab(&self) -> &'a [u8] {
+        self.symtab
+    }
+
+    pub fn strtab(&self) -> &'a [u8] {
+        self.strtab
+    }
+
+    /// Find the function containing `addr`, return its name and the offset
+    pub fn lookup(&self, addr: u64) -> Option<(&'a str, u64)> {
+        self.symtab.chunks_exact(SYM_SIZE).find_map(|sym| {
+            let value = read_u64(sym, 8);
+            let size = read_u64(sym, 16);
+
+            if sym[4] & 0xf != STT_FUNC || addr < value || addr - value >= size.max(1) {
+                return None;
+            }
+
+            let name = u32::from_le_bytes([sym[0], sym[1], sym[2], sym[3]]) as usize;
+            Some((self.name(name)?, addr - value))
+        })
+    }
+
+    fn name(&self, offset: usize) -> Option<&'a str> {
+        let bytes = self.strtab.get(offset..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+        core::str::from_utf8(&bytes[..len]).ok()
+    }
+}
+
+#[inline]
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
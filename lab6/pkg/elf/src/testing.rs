@@ -0,0 +1,149 @@
+//! Hand-made ELF images and page tables for tests
+
+use x86_64::structures::paging::mapper::{MappedFrame, TranslateResult};
+use x86_64::structures::paging::{PageTableFlags, PhysFrame, Translate};
+use x86_64::{PhysAddr, VirtAddr};
+
+pub const PAGE_SIZE: u64 = 0x1000;
+
+pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
+
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+/// Offset of the data after the program headers
+pub const DATA_OFFSET: u64 = 0x200;
+
+/// An x86_64 ELF image, program headers follow the header
+pub struct Image {
+    /// u64 words keep the headers aligned for `xmas_elf`
+    words: Vec<u64>,
+    phnum: u16,
+}
+
+impl Image {
+    pub fn new(ty: u16) -> Self {
+        let mut image = Self {
+            words: vec![0; DATA_OFFSET as usize / 8],
+            phnum: 0,
+        };
+
+        image.write(0, b"\x7fELF\x02\x01\x01");
+        image.write(16, &ty.to_le_bytes());
+        image.write(18, &62u16.to_le_bytes()); // EM_X86_64
+        image.write(20, &1u32.to_le_bytes());
+        image.write(32, &(EHDR_SIZE as u64).to_le_bytes());
+        image.write(52, &(EHDR_SIZE as u16).to_le_bytes());
+        image.write(54, &(PHDR_SIZE as u16).to_le_bytes());
+        image
+    }
+
+    /// Append a program header
+    pub fn segment(
+        &mut self,
+        ty: u32,
+        flags: u32,
+        offset: u64,
+        vaddr: u64,
+        filesz: u64,
+        memsz: u64,
+    ) {
+        let at = EHDR_SIZE + self.phnum as usize * PHDR_SIZE;
+        assert!(at + PHDR_SIZE <= DATA_OFFSET as usize);
+
+        self.write(at, &ty.to_le_bytes());
+        self.write(at + 4, &flags.to_le_bytes());
+        for (i, value) in [offset, vaddr, vaddr, filesz, memsz, PAGE_SIZE]
+            .iter()
+            .enumerate()
+        {
+            self.write(at + 8 + i * 8, &value.to_le_bytes());
+        }
+
+        self.phnum += 1;
+        let phnum = self.phnum;
+        self.write(56, &phnum.to_le_bytes());
+    }
+
+    /// Write bytes at `offset`, growing the image as needed
+    pub fn write(&mut self, offset: usize, data: &[u8]) {
+        let end = (offset + data.len()).div_ceil(8);
+        if self.words.len() < end {
+            self.words.resize(end, 0);
+        }
+        self.bytes_mut()[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    pub fn write_u64s(&mut self, offset: usize, values: &[u64]) {
+        for (i, value) in values.iter().enumerate() {
+            self.write(offset + i * 8, &value.to_le_bytes());
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.words.as_ptr().cast(), self.words.len() * 8) }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(self.words.as_mut_ptr().cast(), self.words.len() * 8)
+        }
+    }
+}
+
+#[repr(C, align(4096))]
+pub struct Frame(pub [u8; PAGE_SIZE as usize]);
+
+/// Pages mapped to frames on the host heap, with no physical offset
+pub struct FakePageTable {
+    pages: Vec<(u64, Box<Frame>)>,
+}
+
+impl FakePageTable {
+    /// Map `count` pages from `start`, each to its own frame
+    pub fn new(start: u64, count: u64) -> Self {
+        let pages = (0..count)
+            .map(|i| {
+                (
+                    start + i * PAGE_SIZE,
+                    Box::new(Frame([0; PAGE_SIZE as usize])),
+                )
+            })
+            .collect();
+        Self { pages }
+    }
+
+    /// The frame backing the page at `vaddr`
+    pub fn frame(&self, vaddr: u64) -> &[u8] {
+        let (_, frame) = self
+            .pages
+            .iter()
+            .find(|(page, _)| *page == vaddr & !(PAGE_SIZE - 1))
+            .expect("page not mapped");
+        &frame.0
+    }
+}
+
+impl Translate for FakePageTable {
+    fn translate(&self, addr: VirtAddr) -> TranslateResult {
+        let page = addr.align_down(PAGE_SIZE).as_u64();
+        match self.pages.iter().find(|(start, _)| *start == page) {
+            Some((_, frame)) => TranslateResult::Mapped {
+                frame: MappedFrame::Size4KiB(PhysFrame::containing_address(PhysAddr::new(
+                    frame.0.as_ptr() as u64,
+                ))),
+                offset: addr.as_u64() - page,
+                flags: PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+            },
+            None => TranslateResult::NotMapped,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use xmas_elf::{ElfFile, program};
+
+use crate::ElfLoadError;
+
+/// Initial image of the thread-local storage from `PT_TLS`
+#[derive(Clone, Copy, Debug)]
+pub struct TlsTemplate<'a> {
+    /// initialised data (`.tdata`), the rest is zero filled (`.tbss`)
+    pub data: &'a [u8],
+    /// size of the whole TLS block
+    pub mem_size: u64,
+    /// alignment of the TLS block, a power of two
+    pub align: u64,
+}
+
+impl TlsTemplate<'_> {
+    /// Size of the TLS block, padded so the thread pointer after it is aligned
+    pub fn block_size(&self) -> u64 {
+        self.mem_size.next_multiple_of(self.align)
+    }
+}
+
+/// Find the TLS template of an ELF file, if it has one
+pub fn tls_template<'a>(elf: &ElfFile<'a>) -> Result<Option<TlsTemplate<'a>>, ElfLoadError> {
+    let mut template = None;
+
+    for segment in elf.program_iter() {
+        if segment.get_type().map_err(ElfLoadError::InvalidSegment)? != program::Type::Tls {
+            continue;
+        }
+
+        if template.is_some() {
+            return Err(ElfLoadError::InvalidSegment("multiple TLS segments"));
+        }
+
+        if segment.file_size() > segment.mem_size() {
+            return Err(ElfLoadError::InvalidSegment(
+                "file size exceeds memory size",
+            ));
+        }
+
+        let offset = segment.offset() as usize;
+        let data = offset
+            .checked_add(segment.file_size() as usize)
+            .and_then(|end| elf.input.get(offset..end))
+            .ok_or(ElfLoadError::InvalidSegment("segment data out of file"))?;
+
+        let align = segment.align().max(1);
+        if !align.is_power_of_two() {
+            return Err(ElfLoadError::InvalidSegment(
+                "TLS alignment is not a power of two",
+            ));
+        }
+
+        template = Some(TlsTemplate {
+            data,
+            mem_size: segment.mem_size(),
+            align,
+        });
+    }
+
+    Ok(template)
+}
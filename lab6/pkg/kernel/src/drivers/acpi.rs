@@ -0,0 +1,602 @@
+//! ACPI tables from the RSDP found by the bootloader
+//!
+//! The MADT is parsed for the addresses of the local APIC and the IO APICs,
+//! the interrupt source overrides of ISA IRQs and the CPUs. The FADT and
+//! the `\_S5` object of the DSDT are used to power off and reset.
+//!
+//! Reference: [OSDev Wiki](https://wiki.osdev.org/MADT),
+//! [Shutdown](https://wiki.osdev.org/Shutdown)
+
+use alloc::vec;
+use alloc::vec::Vec;
+use boot::BootInfo;
+use core::mem::size_of;
+
+use x86_64::instructions::port::Port;
+
+use crate::interrupt::{IOAPIC_ADDR, LAPIC_ADDR};
+use crate::memory::{map_mmio, physical_to_virtual};
+
+const RSDP_SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// Size of the RSDP in ACPI 1.0, covered by its first checksum
+const RSDP_V1_SIZE: usize = 20;
+
+const MADT_SIGNATURE: &[u8; 4] = b"APIC";
+
+/// PCAT_COMPAT flag of the MADT, the 8259 PICs are installed
+const MADT_PCAT_COMPAT: u32 = 1;
+
+const FADT_SIGNATURE: &[u8; 4] = b"FACP";
+
+/// RESET_REG_SUP flag of the FADT, the reset register is valid
+const FADT_RESET_REG_SUP: u32 = 1 << 10;
+
+/// SCI_EN bit of PM1 control, the hardware is in ACPI mode
+const PM1_SCI_EN: u16 = 1;
+
+/// SLP_EN bit of PM1 control, enter the sleep state in SLP_TYP
+const PM1_SLP_EN: u16 = 1 << 13;
+
+static MADT: spin::Once<Madt> = spin::Once::new();
+
+static FADT: spin::Once<Option<Fadt>> = spin::Once::new();
+
+pub fn init(boot_info: &'static BootInfo) {
+    FADT.call_once(|| {
+        let fadt = boot_info
+            .rsdp_addr
+            .and_then(|rsdp| find_table(rsdp, FADT_SIGNATURE))
+            .map(parse_fadt);
+        debug!("{:?}", fadt);
+        fadt
+    });
+
+    let madt = match boot_info
+        .rsdp_addr
+        .and_then(|rsdp| find_table(rsdp, MADT_SIGNATURE))
+    {
+        Some(addr) => parse_madt(addr),
+        None => {
+            warn!("No ACPI MADT, using the default APIC addresses.");
+            Madt::default()
+        }
+    };
+
+    // the registers are outside of the memory map
+    map_mmio(madt.lapic_addr, 0x1000);
+    for ioapic in madt.ioapics.iter() {
+        map_mmio(ioapic.addr, 0x1000);
+    }
+
+    info!(
+        "ACPI: {} CPUs, LAPIC at {:#x}, {} IOAPICs, {} IRQ overrides",
+        madt.cpus.len(),
+        madt.lapic_addr,
+        madt.ioapics.len(),
+        madt.overrides.len()
+    );
+    for cpu in madt.cpus.iter() {
+        debug!("{:?}", cpu);
+    }
+    for ioapic in madt.ioapics.iter() {
+        debug!("{:?}", ioapic);
+    }
+    for irq in madt.overrides.iter() {
+        debug!("{:?}", irq);
+    }
+
+    MADT.call_once(|| madt);
+}
+
+/// The MADT, or the defaults of QEMU if there is none
+pub fn madt() -> &'static Madt {
+    MADT.get().expect("ACPI not initialized")
+}
+
+/// Enter the S5 sleep state, returns if it is not supported
+pub fn power_off() {
+    let Some(fadt) = FADT.get().copied().flatten() else {
+        return;
+    };
+    let Some((slp_typ_a, slp_typ_b)) = fadt.slp_typ else {
+        return;
+    };
+
+    fadt.enable();
+
+    unsafe {
+        Port::<u16>::new(fadt.pm1a_cnt).write(slp_typ_a << 10 | PM1_SLP_EN);
+        if fadt.pm1b_cnt != 0 {
+            Port::<u16>::new(fadt.pm1b_cnt).write(slp_typ_b << 10 | PM1_SLP_EN);
+        }
+    }
+}
+
+/// Write the reset register, returns if it is not supported
+pub fn reset() {
+    let Some(Fadt {
+        reset_reg: Some((reg, value)),
+        ..
+    }) = FADT.get().copied().flatten()
+    else {
+        return;
+    };
+
+    match reg.space {
+        GenericAddress::SYSTEM_MEMORY => {
+            map_mmio(reg.addr, 1);
+            unsafe { (physical_to_virtual(reg.addr) as *mut u8).write_volatile(value) };
+        }
+        GenericAddress::SYSTEM_IO => unsafe { Port::<u8>::new(reg.addr as u16).write(value) },
+        space => warn!(
+            "Unsupported address space of the ACPI reset register: {}",
+            space
+        ),
+    }
+}
+
+/// A processor with a local APIC
+#[derive(Debug, Clone, Copy)]
+pub struct Cpu {
+    pub processor_id: u32,
+    pub apic_id: u32,
+    /// usable now, otherwise it can only be brought online later
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IoApicInfo {
+    pub id: u8,
+    /// physical address of the registers
+    pub addr: u64,
+    /// first global system interrupt handled by this IO APIC
+    pub gsi_base: u32,
+}
+
+/// Route of an ISA IRQ to a global system interrupt
+#[derive(Debug, Clone, Copy)]
+pub struct IrqOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub active_low: bool,
+    pub level: bool,
+}
+
+#[derive(Debug)]
+pub struct Madt {
+    /// physical address of the local APIC registers
+    pub lapic_addr: u64,
+    pub cpus: Vec<Cpu>,
+    pub ioapics: Vec<IoApicInfo>,
+    pub overrides: Vec<IrqOverride>,
+    /// the 8259 PICs are installed and have to be masked
+    pub legacy_pic: bool,
+}
+
+impl Default for Madt {
+    fn default() -> Self {
+        Self {
+            lapic_addr: LAPIC_ADDR,
+            cpus: vec![Cpu {
+                processor_id: 0,
+                apic_id: 0,
+                enabled: true,
+            }],
+            ioapics: vec![IoApicInfo {
+                id: 0,
+                addr: IOAPIC_ADDR,
+                gsi_base: 0,
+            }],
+            overrides: Vec::new(),
+            legacy_pic: true,
+        }
+    }
+}
+
+impl Madt {
+    /// Route of an ISA IRQ, identity mapped, edge triggered
+    /// and active high unless it is overridden
+    pub fn route(&self, irq: u8) -> IrqOverride {
+        self.overrides
+            .iter()
+            .find(|o| o.irq == irq)
+            .copied()
+            .unwrap_or(IrqOverride {
+                irq,
+                gsi: irq as u32,
+                active_low: false,
+                level: false,
+            })
+    }
+
+    /// The IO APIC handling a global system interrupt
+    pub fn ioapic_for(&self, gsi: u32) -> Option<&IoApicInfo> {
+        self.ioapics
+            .iter()
+            .filter(|ioapic| ioapic.gsi_base <= gsi)
+            .max_by_key(|ioapic| ioapic.gsi_base)
+    }
+}
+
+#[repr(C, packed)]
+struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_addr: u32,
+    // ACPI 2.0
+    length: u32,
+    xsdt_addr: u64,
+    ext_checksum: u8,
+    reserved: [u8; 3],
+}
+
+#[repr(C, packed)]
+struct SdtHeader {
+    signature: [u8; 4],
+    length: u32,
+    revision: u8,
+    checksum: u8,
+    oem_id: [u8; 6],
+    oem_table_id: [u8; 8],
+    oem_revision: u32,
+    creator_id: u32,
+    creator_revision: u32,
+}
+
+/// Register location in the FADT
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+struct GenericAddress {
+    space: u8,
+    bit_width: u8,
+    bit_offset: u8,
+    access_size: u8,
+    addr: u64,
+}
+
+impl GenericAddress {
+    const SYSTEM_MEMORY: u8 = 0;
+    const SYSTEM_IO: u8 = 1;
+}
+
+/// FADT up to the fields of ACPI 2.0 used here
+#[repr(C, packed)]
+struct FadtTable {
+    header: SdtHeader,
+    firmware_ctrl: u32,
+    dsdt: u32,
+    reserved: u8,
+    preferred_pm_profile: u8,
+    sci_int: u16,
+    smi_cmd: u32,
+    acpi_enable: u8,
+    acpi_disable: u8,
+    s4bios_req: u8,
+    pstate_cnt: u8,
+    pm1a_evt_blk: u32,
+    pm1b_evt_blk: u32,
+    pm1a_cnt_blk: u32,
+    pm1b_cnt_blk: u32,
+    pm2_cnt_blk: u32,
+    pm_tmr_blk: u32,
+    gpe0_blk: u32,
+    gpe1_blk: u32,
+    pm1_evt_len: u8,
+    pm1_cnt_len: u8,
+    pm2_cnt_len: u8,
+    pm_tmr_len: u8,
+    gpe0_blk_len: u8,
+    gpe1_blk_len: u8,
+    gpe1_base: u8,
+    cst_cnt: u8,
+    p_lvl2_lat: u16,
+    p_lvl3_lat: u16,
+    flush_size: u16,
+    flush_stride: u16,
+    duty_offset: u8,
+    duty_width: u8,
+    day_alrm: u8,
+    mon_alrm: u8,
+    century: u8,
+    iapc_boot_arch: u16,
+    reserved2: u8,
+    flags: u32,
+    // ACPI 2.0
+    reset_reg: GenericAddress,
+    reset_value: u8,
+    arm_boot_arch: u16,
+    minor_version: u8,
+    x_firmware_ctrl: u64,
+    x_dsdt: u64,
+}
+
+/// Power management registers from the FADT
+#[derive(Debug, Clone, Copy)]
+struct Fadt {
+    smi_cmd: u16,
+    acpi_enable: u8,
+    pm1a_cnt: u16,
+    pm1b_cnt: u16,
+    /// SLP_TYPa and SLP_TYPb of the S5 state, from the DSDT
+    slp_typ: Option<(u16, u16)>,
+    reset_reg: Option<(GenericAddress, u8)>,
+}
+
+impl Fadt {
+    /// Switch the hardware to ACPI mode if the firmware did not
+    fn enable(&self) {
+        let mut pm1a_cnt = Port::<u16>::new(self.pm1a_cnt);
+        if unsafe { pm1a_cnt.read() } & PM1_SCI_EN != 0 || self.smi_cmd == 0 {
+            return;
+        }
+
+        unsafe { Port::<u8>::new(self.smi_cmd).write(self.acpi_enable) };
+        for _ in 0..0x100000 {
+            if unsafe { pm1a_cnt.read() } & PM1_SCI_EN != 0 {
+                return;
+            }
+            core::hint::spin_loop();
+        }
+        warn!("Failed to enable ACPI mode.");
+    }
+}
+
+#[repr(C, packed)]
+struct MadtHeader {
+    header: SdtHeader,
+    lapic_addr: u32,
+    flags: u32,
+}
+
+/// Header of the entries after `MadtHeader`
+#[repr(C, packed)]
+struct EntryHeader {
+    ty: u8,
+    length: u8,
+}
+
+#[repr(C, packed)]
+struct LocalApicEntry {
+    header: EntryHeader,
+    processor_id: u8,
+    apic_id: u8,
+    flags: u32,
+}
+
+#[repr(C, packed)]
+struct IoApicEntry {
+    header: EntryHeader,
+    id: u8,
+    reserved: u8,
+    addr: u32,
+    gsi_base: u32,
+}
+
+#[repr(C, packed)]
+struct OverrideEntry {
+    header: EntryHeader,
+    bus: u8,
+    irq: u8,
+    gsi: u32,
+    flags: u16,
+}
+
+#[repr(C, packed)]
+struct LapicAddrEntry {
+    header: EntryHeader,
+    reserved: u16,
+    addr: u64,
+}
+
+#[repr(C, packed)]
+struct X2ApicEntry {
+    header: EntryHeader,
+    reserved: u16,
+    apic_id: u32,
+    flags: u32,
+    processor_id: u32,
+}
+
+const ENTRY_LOCAL_APIC: u8 = 0;
+const ENTRY_IO_APIC: u8 = 1;
+const ENTRY_OVERRIDE: u8 = 2;
+const ENTRY_LAPIC_ADDR: u8 = 5;
+const ENTRY_X2APIC: u8 = 9;
+
+/// Read a table at a physical address
+fn read<T>(addr: u64) -> T {
+    unsafe { (physical_to_virtual(addr) as *const T).read_unaligned() }
+}
+
+/// The sum of all bytes of a valid table is zero
+fn checksum(addr: u64, len: usize) -> bool {
+    let bytes = unsafe { core::slice::from_raw_parts(physical_to_virtual(addr) as *const u8, len) };
+    bytes.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+/// Header of a system description table, if its checksum is valid
+fn read_sdt(addr: u64) -> Option<SdtHeader> {
+    let header: SdtHeader = read(addr);
+    let length = header.length as usize;
+
+    if length < size_of::<SdtHeader>() || !checksum(addr, length) {
+        warn!(
+            "Invalid ACPI table {} at {:#x}",
+            core::str::from_utf8(&header.signature).unwrap_or("????"),
+            addr
+        );
+        return None;
+    }
+
+    Some(header)
+}
+
+/// Find a table in the XSDT, or in the RSDT before ACPI 2.0
+fn find_table(rsdp_addr: u64, signature: &[u8; 4]) -> Option<u64> {
+    let rsdp: Rsdp = read(rsdp_addr);
+
+    if &rsdp.signature != RSDP_SIGNATURE || !checksum(rsdp_addr, RSDP_V1_SIZE) {
+        warn!("Invalid ACPI RSDP at {:#x}", rsdp_addr);
+        return None;
+    }
+
+    let (sdt_addr, entry_size) = if rsdp.revision >= 2 && rsdp.xsdt_addr != 0 {
+        (rsdp.xsdt_addr, size_of::<u64>())
+    } else {
+        (rsdp.rsdt_addr as u64, size_of::<u32>())
+    };
+
+    let header = read_sdt(sdt_addr)?;
+    let entries = sdt_addr + size_of::<SdtHeader>() as u64;
+    let count = (header.length as usize - size_of::<SdtHeader>()) / entry_size;
+
+    (0..count)
+        .map(|i| {
+            let entry = entries + (i * entry_size) as u64;
+            match entry_size {
+                8 => read::<u64>(entry),
+                _ => read::<u32>(entry) as u64,
+            }
+        })
+        .find(|&addr| read_sdt(addr).is_some_and(|sdt| &sdt.signature == signature))
+}
+
+fn parse_fadt(addr: u64) -> Fadt {
+    let table: FadtTable = read(addr);
+    let length = table.header.length as usize;
+
+    // fields of ACPI 2.0 are only valid in a table long enough
+    let has = |end: usize| length >= end;
+
+    let reset_reg = (has(core::mem::offset_of!(FadtTable, arm_boot_arch))
+        && table.flags & FADT_RESET_REG_SUP != 0)
+        .then_some((table.reset_reg, table.reset_value));
+
+    let dsdt = if has(size_of::<FadtTable>()) && table.x_dsdt != 0 {
+        table.x_dsdt
+    } else {
+        table.dsdt as u64
+    };
+
+    Fadt {
+        smi_cmd: table.smi_cmd as u16,
+        acpi_enable: table.acpi_enable,
+        pm1a_cnt: table.pm1a_cnt_blk as u16,
+        pm1b_cnt: table.pm1b_cnt_blk as u16,
+        slp_typ: find_s5(dsdt),
+        reset_reg,
+    }
+}
+
+/// Find SLP_TYPa and SLP_TYPb of `\_S5` in the DSDT
+///
+/// Instead of interpreting AML, this looks for the usual encoding:
+/// `NameOp _S5_ PackageOp PkgLength NumElements [BytePrefix] a [BytePrefix] b`
+fn find_s5(dsdt: u64) -> Option<(u16, u16)> {
+    const NAME_OP: u8 = 0x08;
+    const PACKAGE_OP: u8 = 0x12;
+    const BYTE_PREFIX: u8 = 0x0A;
+
+    let header = read_sdt(dsdt)?;
+    let aml = unsafe {
+        core::slice::from_raw_parts(
+            physical_to_virtual(dsdt) as *const u8,
+            header.length as usize,
+        )
+    };
+
+    let start = (1..=aml.len().saturating_sub(5)).find(|&i| {
+        aml[i..i + 5] == [b'_', b'S', b'5', b'_', PACKAGE_OP]
+            && (aml[..i].ends_with(&[NAME_OP]) || aml[..i].ends_with(&[NAME_OP, b'\\']))
+    })?;
+
+    let mut bytes = aml[start + 5..].iter().copied();
+
+    // the top two bits of the first byte are the count of following bytes
+    let pkg_length = bytes.next()?;
+    let mut bytes = bytes.skip((pkg_length >> 6) as usize + 1);
+
+    let mut next_value = || match bytes.next()? {
+        BYTE_PREFIX => bytes.next(),
+        value => Some(value),
+    };
+
+    Some((next_value()? as u16, next_value()? as u16))
+}
+
+fn parse_madt(addr: u64) -> Madt {
+    let header: MadtHeader = read(addr);
+    let end = addr + header.header.length as u64;
+
+    let mut madt = Madt {
+        lapic_addr: header.lapic_addr as u64,
+        cpus: Vec::new(),
+        ioapics: Vec::new(),
+        overrides: Vec::new(),
+        legacy_pic: header.flags & MADT_PCAT_COMPAT != 0,
+    };
+
+    let mut entry = addr + size_of::<MadtHeader>() as u64;
+    while entry + size_of::<EntryHeader>() as u64 <= end {
+        let EntryHeader { ty, length } = read(entry);
+        if length < size_of::<EntryHeader>() as u8 || entry + length as u64 > end {
+            warn!("Invalid MADT entry at {:#x}", entry);
+            break;
+        }
+
+        match ty {
+            ENTRY_LOCAL_APIC => {
+                let lapic: LocalApicEntry = read(entry);
+                madt.cpus.push(Cpu {
+                    processor_id: lapic.processor_id as u32,
+                    apic_id: lapic.apic_id as u32,
+                    enabled: lapic.flags & 1 != 0,
+                });
+            }
+            ENTRY_X2APIC => {
+                let x2apic: X2ApicEntry = read(entry);
+                madt.cpus.push(Cpu {
+                    processor_id: x2apic.processor_id,
+                    apic_id: x2apic.apic_id,
+                    enabled: x2apic.flags & 1 != 0,
+                });
+            }
+            ENTRY_IO_APIC => {
+                let ioapic: IoApicEntry = read(entry);
+                madt.ioapics.push(IoApicInfo {
+                    id: ioapic.id,
+                    addr: ioapic.addr as u64,
+                    gsi_base: ioapic.gsi_base,
+                });
+            }
+            ENTRY_OVERRIDE => {
+                let irq: OverrideEntry = read(entry);
+                // polarity in bits 0-1 and trigger mode in bits 2-3,
+                // 0b11 is active low and level triggered
+                madt.overrides.push(IrqOverride {
+                    irq: irq.irq,
+                    gsi: irq.gsi,
+                    active_low: irq.flags & 0b11 == 0b11,
+                    level: (irq.flags >> 2) & 0b11 == 0b11,
+                });
+            }
+            ENTRY_LAPIC_ADDR => {
+                let lapic: LapicAddrEntry = read(entry);
+                madt.lapic_addr = lapic.addr;
+            }
+            _ => {}
+        }
+
+        entry += length as u64;
+    }
+
+    if madt.ioapics.is_empty() {
+        warn!("No IOAPIC in the MADT, using the default address.");
+        madt.ioapics = Madt::default().ioapics;
+    }
+
+    madt
+}
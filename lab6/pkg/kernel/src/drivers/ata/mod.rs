@@ -13,6 +13,11 @@ use bus::AtaBus;
 use consts::AtaDeviceType;
 use spin::Mutex;
 
+use crate::utils::params::Param;
+
+/// Device of the root filesystem, see `parse_device` here and in `ramdisk`
+pub static ROOT: Param<&str> = Param::new("root", "hda1");
+
 lazy_static! {
     pub static ref BUSES: [Mutex<AtaBus>; 2] = {
         let buses = [
@@ -26,6 +31,25 @@ lazy_static! {
     };
 }
 
+/// Parse a device name into (bus, drive, partition)
+///
+/// `hda` to `hdd` are the master and slave drives of the two buses,
+/// followed by the partition number from 1, or nothing for the whole drive.
+pub fn parse_device(name: &str) -> Option<(u8, u8, Option<usize>)> {
+    let name = name.strip_prefix("/dev/").unwrap_or(name);
+    let index = match name.strip_prefix("hd")?.as_bytes().first()? {
+        c @ b'a'..=b'd' => c - b'a',
+        _ => return None,
+    };
+
+    let partition = match &name[3..] {
+        "" => None,
+        num => Some(num.parse::<usize>().ok().filter(|&n| n > 0)?),
+    };
+
+    Some((index / 2, index % 2, partition))
+}
+
 #[derive(Clone)]
 pub struct AtaDrive {
     pub bus: u8,
@@ -0,0 +1,400 @@
+//! Text console on the framebuffer from the bootloader
+//!
+//! Everything printed to the serial console is also drawn here with the
+//! built-in font. The ANSI escape sequences used by the logger and the
+//! shell are handled: SGR colors, cursor position and erasing.
+
+mod font;
+
+use boot::{BootInfo, GraphicInfo, PixelFormat};
+use core::fmt;
+
+use crate::memory::physical_to_virtual;
+
+/// Maximum count of parameters in an escape sequence
+const MAX_PARAMS: usize = 8;
+
+/// VGA palette of the 16 ANSI colors, as `0xRRGGBB`
+#[rustfmt::skip]
+const PALETTE: [u32; 16] = [
+    0x000000, 0xAA0000, 0x00AA00, 0xAA5500, 0x0000AA, 0xAA00AA, 0x00AAAA, 0xAAAAAA,
+    0x555555, 0xFF5555, 0x55FF55, 0xFFFF55, 0x5555FF, 0xFF55FF, 0x55FFFF, 0xFFFFFF,
+];
+
+const DEFAULT_FG: u8 = 7;
+const DEFAULT_BG: u8 = 0;
+
+once_mutex!(pub CONSOLE: FbConsole);
+
+guard_access_fn!(pub get_console(CONSOLE: FbConsole));
+
+pub fn init(boot_info: &'static BootInfo) {
+    let Some(info) = boot_info.graphic_info else {
+        info!("No framebuffer, console on serial only.");
+        return;
+    };
+
+    let Some(fb) = FrameBuffer::new(&info) else {
+        warn!("Unsupported pixel format: {:?}", info.mode.pixel_format());
+        return;
+    };
+
+    let mut console = FbConsole::new(fb);
+    console.clear();
+    console.toggle_cursor();
+
+    info!(
+        "Framebuffer console: {}x{} pixels, {}x{} characters",
+        console.fb.width, console.fb.height, console.cols, console.rows
+    );
+    init_CONSOLE(console);
+}
+
+/// Shift and width of a color channel in a pixel
+#[derive(Clone, Copy)]
+struct Channel {
+    shift: u32,
+    bits: u32,
+}
+
+impl Channel {
+    fn from_mask(mask: u32) -> Self {
+        Self {
+            shift: mask.trailing_zeros(),
+            bits: mask.count_ones().min(8),
+        }
+    }
+
+    fn encode(&self, value: u32) -> u32 {
+        (value >> (8 - self.bits)) << self.shift
+    }
+}
+
+/// 32 bits per pixel framebuffer in the kernel address space
+struct FrameBuffer {
+    base: *mut u32,
+    width: usize,
+    height: usize,
+    /// pixels per scan line
+    stride: usize,
+    red: Channel,
+    green: Channel,
+    blue: Channel,
+}
+
+impl FrameBuffer {
+    fn new(info: &GraphicInfo) -> Option<Self> {
+        let (red, green, blue) = match info.mode.pixel_format() {
+            PixelFormat::Rgb => (0xFF, 0xFF00, 0xFF0000),
+            PixelFormat::Bgr => (0xFF0000, 0xFF00, 0xFF),
+            PixelFormat::Bitmask => {
+                let mask = info.mode.pixel_bitmask()?;
+                (mask.red, mask.green, mask.blue)
+            }
+            PixelFormat::BltOnly => return None,
+        };
+
+        let (width, height) = info.mode.resolution();
+        let stride = info.mode.stride();
+
+        if (stride * height * 4) as u64 > info.fb_size {
+            return None;
+        }
+
+        Some(Self {
+            base: physical_to_virtual(info.fb_addr) as *mut u32,
+            width,
+            height,
+            stride,
+            red: Channel::from_mask(red),
+            green: Channel::from_mask(green),
+            blue: Channel::from_mask(blue),
+        })
+    }
+
+    /// Pixel value of a `0xRRGGBB` color
+    fn pixel(&self, rgb: u32) -> u32 {
+        self.red.encode((rgb >> 16) & 0xFF)
+            | self.green.encode((rgb >> 8) & 0xFF)
+            | self.blue.encode(rgb & 0xFF)
+    }
+
+    fn offset(&self, x: usize, y: usize) -> usize {
+        y * self.stride + x
+    }
+
+    fn write(&mut self, x: usize, y: usize, pixel: u32) {
+        unsafe { self.base.add(self.offset(x, y)).write_volatile(pixel) };
+    }
+
+    fn read(&self, x: usize, y: usize) -> u32 {
+        unsafe { self.base.add(self.offset(x, y)).read_volatile() }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, pixel: u32) {
+        for y in y..y + height {
+            for x in x..x + width {
+                self.write(x, y, pixel);
+            }
+        }
+    }
+
+    /// Move `lines` scan lines starting at `src` up to `dst`
+    fn move_up(&mut self, src: usize, dst: usize, lines: usize) {
+        unsafe {
+            core::ptr::copy(
+                self.base.add(self.offset(0, src)),
+                self.base.add(self.offset(0, dst)),
+                lines * self.stride,
+            )
+        };
+    }
+}
+
+/// State of the escape sequence parser
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// after `ESC`
+    Start,
+    /// after `ESC [`, reading parameters
+    Csi,
+}
+
+pub struct FbConsole {
+    fb: FrameBuffer,
+    cols: usize,
+    rows: usize,
+    col: usize,
+    row: usize,
+    fg: u8,
+    bg: u8,
+    bold: bool,
+    escape: Escape,
+    params: [usize; MAX_PARAMS],
+    param_count: usize,
+}
+
+// the framebuffer is only accessed with the console locked
+unsafe impl Send for FbConsole {}
+
+impl FbConsole {
+    fn new(fb: FrameBuffer) -> Self {
+        Self {
+            cols: fb.width / font::WIDTH,
+            rows: fb.height / font::HEIGHT,
+            fb,
+            col: 0,
+            row: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            escape: Escape::None,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+        }
+    }
+
+    fn fg_pixel(&self) -> u32 {
+        let fg = if self.bold && self.fg < 8 {
+            self.fg + 8
+        } else {
+            self.fg
+        };
+        self.fb.pixel(PALETTE[fg as usize])
+    }
+
+    fn bg_pixel(&self) -> u32 {
+        self.fb.pixel(PALETTE[self.bg as usize])
+    }
+
+    fn clear(&mut self) {
+        self.erase_rows(0, self.rows);
+        self.col = 0;
+        self.row = 0;
+    }
+
+    fn erase_rows(&mut self, start: usize, end: usize) {
+        let bg = self.bg_pixel();
+        let y = start * font::HEIGHT;
+        let height = (end - start) * font::HEIGHT;
+        self.fb.fill(0, y, self.cols * font::WIDTH, height, bg);
+    }
+
+    /// Erase the cells `start..end` of the current row
+    fn erase_cols(&mut self, start: usize, end: usize) {
+        let bg = self.bg_pixel();
+        let x = start * font::WIDTH;
+        let width = (end - start) * font::WIDTH;
+        self.fb
+            .fill(x, self.row * font::HEIGHT, width, font::HEIGHT, bg);
+    }
+
+    fn draw_char(&mut self, c: char) {
+        let glyph = font::glyph(c);
+        let (fg, bg) = (self.fg_pixel(), self.bg_pixel());
+        let x = self.col * font::WIDTH;
+        let y = self.row * font::HEIGHT;
+
+        for dy in 0..font::HEIGHT {
+            let bits = glyph[dy * 8 / font::HEIGHT];
+            for dx in 0..font::WIDTH {
+                let pixel = if bits & (1 << dx) != 0 { fg } else { bg };
+                self.fb.write(x + dx, y + dy, pixel);
+            }
+        }
+    }
+
+    /// Invert the underline of the current cell, the cursor is shown
+    /// between writes
+    fn toggle_cursor(&mut self) {
+        let x = self.col.min(self.cols - 1) * font::WIDTH;
+        let y = self.row * font::HEIGHT + font::HEIGHT - 2;
+        let mask = self.fb.pixel(0xFFFFFF);
+
+        for y in y..y + 2 {
+            for x in x..x + font::WIDTH {
+                let pixel = self.fb.read(x, y);
+                self.fb.write(x, y, pixel ^ mask);
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+            return;
+        }
+
+        self.fb
+            .move_up(font::HEIGHT, 0, (self.rows - 1) * font::HEIGHT);
+        self.erase_rows(self.rows - 1, self.rows);
+    }
+
+    fn put_char(&mut self, c: char) {
+        match self.escape {
+            Escape::None => self.put_text(c),
+            Escape::Start if c == '[' => {
+                self.params = [0; MAX_PARAMS];
+                self.param_count = 0;
+                self.escape = Escape::Csi;
+            }
+            // only CSI sequences are supported
+            Escape::Start => self.escape = Escape::None,
+            Escape::Csi => self.put_csi(c),
+        }
+    }
+
+    fn put_text(&mut self, c: char) {
+        match c {
+            '\x1b' => self.escape = Escape::Start,
+            // user programs end lines with `\n` alone
+            '\n' => self.new_line(),
+            '\r' => self.col = 0,
+            '\x08' => self.col = self.col.saturating_sub(1),
+            '\t' => {
+                for _ in 0..8 - self.col % 8 {
+                    self.put_text(' ');
+                }
+            }
+            c if c.is_control() => {}
+            c => {
+                if self.col >= self.cols {
+                    self.new_line();
+                }
+                self.draw_char(c);
+                self.col += 1;
+            }
+        }
+    }
+
+    fn put_csi(&mut self, c: char) {
+        match c {
+            '0'..='9' => {
+                let param = &mut self.params[self.param_count.min(MAX_PARAMS - 1)];
+                *param = param.saturating_mul(10) + (c as usize - '0' as usize);
+            }
+            ';' => self.param_count += 1,
+            // private markers, e.g. `?` in `ESC [ ? 25 h`
+            '\x20'..='\x3f' => {}
+            '\x40'..='\x7e' => {
+                self.param_count = (self.param_count + 1).min(MAX_PARAMS);
+                self.escape = Escape::None;
+                self.execute_csi(c);
+            }
+            _ => self.escape = Escape::None,
+        }
+    }
+
+    /// The `index`-th parameter, or `default` if it is missing or zero
+    fn param(&self, index: usize, default: usize) -> usize {
+        match self.params[..self.param_count].get(index) {
+            Some(&n) if n > 0 => n,
+            _ => default,
+        }
+    }
+
+    fn execute_csi(&mut self, command: char) {
+        match command {
+            'm' => self.select_graphic_rendition(),
+            'H' | 'f' => {
+                self.row = self.param(0, 1).min(self.rows) - 1;
+                self.col = self.param(1, 1).min(self.cols) - 1;
+            }
+            'A' => self.row = self.row.saturating_sub(self.param(0, 1)),
+            'B' => self.row = (self.row + self.param(0, 1)).min(self.rows - 1),
+            'C' => self.col = (self.col + self.param(0, 1)).min(self.cols - 1),
+            'D' => self.col = self.col.saturating_sub(self.param(0, 1)),
+            'J' => match self.param(0, 0) {
+                0 => {
+                    self.erase_cols(self.col.min(self.cols), self.cols);
+                    self.erase_rows(self.row + 1, self.rows);
+                }
+                1 => {
+                    self.erase_rows(0, self.row);
+                    self.erase_cols(0, (self.col + 1).min(self.cols));
+                }
+                _ => self.erase_rows(0, self.rows),
+            },
+            'K' => match self.param(0, 0) {
+                0 => self.erase_cols(self.col.min(self.cols), self.cols),
+                1 => self.erase_cols(0, (self.col + 1).min(self.cols)),
+                _ => self.erase_cols(0, self.cols),
+            },
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self) {
+        for i in 0..self.param_count {
+            match self.params[i] {
+                0 => {
+                    self.fg = DEFAULT_FG;
+                    self.bg = DEFAULT_BG;
+                    self.bold = false;
+                }
+                1 => self.bold = true,
+                22 => self.bold = false,
+                n @ 30..=37 => self.fg = (n - 30) as u8,
+                39 => self.fg = DEFAULT_FG,
+                n @ 40..=47 => self.bg = (n - 40) as u8,
+                49 => self.bg = DEFAULT_BG,
+                n @ 90..=97 => self.fg = (n - 90 + 8) as u8,
+                n @ 100..=107 => self.bg = (n - 100 + 8) as u8,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Write for FbConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.toggle_cursor();
+        for c in s.chars() {
+            self.put_char(c);
+        }
+        self.toggle_cursor();
+        Ok(())
+    }
+}
@@ -1,4 +1,3 @@
-use crate::drivers::serial;
 use alloc::string::String;
 use core::hint::spin_loop;
 use crossbeam_queue::ArrayQueue;
@@ -44,7 +43,7 @@ pub fn get_line() -> String {
         if key == 0x08 || key == 0x7F {
             if !line.is_empty() {
                 line.pop();
-                serial::backspace();
+                print!("\x08 \x08");
             }
             continue;
         }
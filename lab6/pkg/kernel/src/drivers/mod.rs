@@ -1,4 +1,9 @@
+pub mod acpi;
 pub mod ata;
+pub mod fbcon;
 pub mod input;
+pub mod pit;
+pub mod power;
+pub mod ramdisk;
 pub mod serial;
-mod uart16550;
+pub mod uart16550;
@@ -0,0 +1,60 @@
+//! 8254 programmable interval timer
+//!
+//! Only channel 2 is used, as a reference clock to calibrate the APIC
+//! timer and the TSC at boot. Its output can be polled through the
+//! keyboard controller port without enabling any interrupt.
+
+use core::hint::spin_loop;
+use x86_64::instructions::port::Port;
+
+/// Input frequency of the PIT
+pub const PIT_HZ: u64 = 1_193_182;
+
+/// Iterations to wait for the countdown before giving up
+const WAIT_TIMEOUT: usize = 0x10000000;
+
+/// Channel 2 gate and speaker control
+const PORT_CONTROL: u16 = 0x61;
+const PORT_CHANNEL2: u16 = 0x42;
+const PORT_COMMAND: u16 = 0x43;
+
+const GATE2: u8 = 1 << 0;
+const SPEAKER: u8 = 1 << 1;
+const OUT2: u8 = 1 << 5;
+
+/// Channel 2, lobyte/hibyte access, mode 0 (interrupt on terminal count)
+const CHANNEL2_ONESHOT: u8 = 0b1011_0000;
+
+/// Busy wait for `ms` milliseconds, at most 54 ms
+///
+/// Returns false if the PIT did not count down, e.g. it is not emulated.
+pub fn wait_ms(ms: u64) -> bool {
+    let count = (PIT_HZ * ms / 1000).clamp(1, u16::MAX as u64) as u16;
+
+    let mut control = Port::<u8>::new(PORT_CONTROL);
+    let mut command = Port::<u8>::new(PORT_COMMAND);
+    let mut channel = Port::<u8>::new(PORT_CHANNEL2);
+
+    unsafe {
+        // stop the countdown and keep the speaker off
+        let value = control.read();
+        control.write(value & !(SPEAKER | GATE2));
+
+        command.write(CHANNEL2_ONESHOT);
+        channel.write(count as u8);
+        channel.write((count >> 8) as u8);
+
+        // a rising edge of the gate starts the countdown
+        let value = control.read();
+        control.write(value | GATE2);
+    }
+
+    for _ in 0..WAIT_TIMEOUT {
+        if unsafe { control.read() } & OUT2 != 0 {
+            return true;
+        }
+        spin_loop();
+    }
+
+    false
+}
@@ -0,0 +1,114 @@
+//! Power off and reboot
+//!
+//! The method from the `reboot` parameter is tried first, then UEFI
+//! runtime services, the ACPI registers and the keyboard controller.
+
+use core::hint::spin_loop;
+use syscall_def::power::RebootMode;
+use uefi::{Status, runtime::ResetType};
+use x86_64::instructions::port::Port;
+
+use super::acpi;
+use crate::utils::params::{Param, ParamValue};
+
+/// Preferred way to reset the machine, see `ResetMethod`
+pub static REBOOT: Param<ResetMethod> = Param::new("reboot", ResetMethod::Efi);
+
+/// Iterations to wait for a reset to take effect
+const RESET_TIMEOUT: usize = 0x1000000;
+
+/// The mode requested by `Syscall::Reboot`, done when init exits
+static REQUEST: spin::Once<RebootMode> = spin::Once::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetMethod {
+    /// UEFI `ResetSystem`
+    Efi,
+    /// The reset register and the S5 sleep state of ACPI
+    Acpi,
+    /// Pulse the reset line of the 8042 keyboard controller
+    Kbd,
+}
+
+impl ParamValue for ResetMethod {
+    fn parse(value: &'static str) -> Option<Self> {
+        match value {
+            "efi" => Some(Self::Efi),
+            "acpi" => Some(Self::Acpi),
+            "kbd" => Some(Self::Kbd),
+            _ => None,
+        }
+    }
+}
+
+/// Request to power off or reboot, fails if another mode is requested
+pub fn request(mode: RebootMode) -> bool {
+    *REQUEST.call_once(|| mode) == mode
+}
+
+pub fn requested() -> Option<RebootMode> {
+    REQUEST.get().copied()
+}
+
+/// Reset the machine, all processes should have been stopped
+pub fn reset(mode: RebootMode) -> ! {
+    let preferred = REBOOT.get();
+    let fallbacks = [ResetMethod::Efi, ResetMethod::Acpi, ResetMethod::Kbd];
+
+    if mode != RebootMode::Halt {
+        for method in [preferred]
+            .into_iter()
+            .chain(fallbacks.into_iter().filter(|&m| m != preferred))
+        {
+            debug!("Trying to {:?} with {:?}", mode, method);
+            try_reset(method, mode);
+        }
+        error!("Failed to {:?}.", mode);
+    }
+
+    warn!("System halted.");
+    x86_64::instructions::interrupts::disable();
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Returns if the method is not available or did not work
+fn try_reset(method: ResetMethod, mode: RebootMode) {
+    match (method, mode) {
+        (ResetMethod::Efi, _) if has_runtime_services() => {
+            let ty = match mode {
+                RebootMode::Reboot => ResetType::COLD,
+                _ => ResetType::SHUTDOWN,
+            };
+            uefi::runtime::reset(ty, Status::SUCCESS, None);
+        }
+        (ResetMethod::Acpi, RebootMode::PowerOff) => acpi::power_off(),
+        (ResetMethod::Acpi, RebootMode::Reboot) => acpi::reset(),
+        (ResetMethod::Kbd, RebootMode::Reboot) => kbd_reset(),
+        _ => return,
+    }
+
+    for _ in 0..RESET_TIMEOUT {
+        spin_loop();
+    }
+}
+
+fn has_runtime_services() -> bool {
+    uefi::table::system_table_raw()
+        .is_some_and(|st| !unsafe { st.as_ref() }.runtime_services.is_null())
+}
+
+fn kbd_reset() {
+    let mut status = Port::<u8>::new(0x64);
+
+    // wait for the input buffer to be empty
+    for _ in 0..RESET_TIMEOUT {
+        if unsafe { status.read() } & 0b10 == 0 {
+            break;
+        }
+        spin_loop();
+    }
+
+    unsafe { status.write(0xFE) };
+}
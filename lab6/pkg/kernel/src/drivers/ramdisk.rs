@@ -0,0 +1,98 @@
+//! Initial ramdisk loaded by the bootloader
+//!
+//! The image is used as a disk, e.g. with an MBR and FAT16 partitions,
+//! so the kernel can boot without an ATA drive.
+
+use boot::BootInfo;
+use storage::{Block512, BlockDevice, FsError, FsResult};
+
+use crate::memory::physical_to_virtual;
+
+const BLOCK_SIZE: usize = 512;
+
+static RAMDISK: spin::Once<RamDisk> = spin::Once::new();
+
+pub fn init(boot_info: &'static BootInfo) {
+    let Some((addr, len)) = boot_info.initrd else {
+        return;
+    };
+
+    if len % BLOCK_SIZE as u64 != 0 {
+        warn!(
+            "Initrd size is not a multiple of {} bytes, the tail is ignored.",
+            BLOCK_SIZE
+        );
+    }
+
+    let ramdisk = RAMDISK.call_once(|| RamDisk {
+        addr: physical_to_virtual(addr),
+        blocks: (len / BLOCK_SIZE as u64) as usize,
+    });
+
+    info!("Initrd loaded: {}", ramdisk);
+}
+
+/// The initial ramdisk, if the bootloader loaded one
+pub fn get() -> Option<RamDisk> {
+    RAMDISK.get().copied()
+}
+
+/// Parse a device name into the partition number
+///
+/// `ram0` is the whole ramdisk, `ram0p1` is its first partition.
+pub fn parse_device(name: &str) -> Option<Option<usize>> {
+    let name = name.strip_prefix("/dev/").unwrap_or(name);
+
+    match name.strip_prefix("ram0")? {
+        "" => Some(None),
+        part => Some(Some(
+            part.strip_prefix('p')?
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)?,
+        )),
+    }
+}
+
+/// A block device in the memory of the initial ramdisk
+#[derive(Clone, Copy)]
+pub struct RamDisk {
+    /// virtual address of the first block
+    addr: u64,
+    blocks: usize,
+}
+
+impl RamDisk {
+    fn block_ptr(&self, offset: usize) -> FsResult<*mut u8> {
+        if offset >= self.blocks {
+            return Err(FsError::InvalidOffset);
+        }
+
+        Ok((self.addr + (offset * BLOCK_SIZE) as u64) as *mut u8)
+    }
+}
+
+impl core::fmt::Display for RamDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let (size, unit) = crate::humanized_size((self.blocks * BLOCK_SIZE) as u64);
+        write!(f, "RamDisk at {:#x} ({} {})", self.addr, size, unit)
+    }
+}
+
+impl BlockDevice<Block512> for RamDisk {
+    fn block_count(&self) -> FsResult<usize> {
+        Ok(self.blocks)
+    }
+
+    fn read_block(&self, offset: usize, block: &mut Block512) -> FsResult {
+        let src = self.block_ptr(offset)?;
+        unsafe { core::ptr::copy_nonoverlapping(src, block.as_mut().as_mut_ptr(), BLOCK_SIZE) };
+        Ok(())
+    }
+
+    fn write_block(&self, offset: usize, block: &Block512) -> FsResult {
+        let dst = self.block_ptr(offset)?;
+        unsafe { core::ptr::copy_nonoverlapping(block.as_ref().as_ptr(), dst, BLOCK_SIZE) };
+        Ok(())
+    }
+}
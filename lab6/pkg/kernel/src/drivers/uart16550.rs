@@ -11,6 +11,12 @@ pub struct SerialPort<const BASE_ADDR: u16> {
     line_status: Port<u8>,
 }
 
+impl<const BASE_ADDR: u16> Default for SerialPort<BASE_ADDR> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<const BASE_ADDR: u16> SerialPort<BASE_ADDR> {
     pub const fn new() -> Self {
         Self {
@@ -25,6 +31,13 @@ impl<const BASE_ADDR: u16> SerialPort<BASE_ADDR> {
 
     /// Initializes the serial port.
     pub fn init(&mut self) {
+        if !self.try_init() {
+            panic!("Serial port initialization failed.");
+        }
+    }
+
+    /// Initializes the serial port, return false if the chip is missing.
+    pub fn try_init(&mut self) -> bool {
         // FIXME: Initialize the serial port
         unsafe {
             self.int_enable.write(0x00_u8); // Disable all interrupts
@@ -37,11 +50,12 @@ impl<const BASE_ADDR: u16> SerialPort<BASE_ADDR> {
             self.modem_control.write(0x1E_u8); // Set in loopback mode, test the serial chip
             self.data.write(0xAE_u8); // Test serial chip (send byte 0xAE and check if serial returns same byte)
             if self.data.read() != 0xAE_u8 {
-                panic!("Serial port initialization failed.");
+                return false;
             }
             self.modem_control.write(0x0F_u8);
             self.int_enable.write(0x01_u8); // Enable interrupts
         }
+        true
     }
 
     /// Sends a byte on the serial port.
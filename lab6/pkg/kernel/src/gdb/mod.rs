@@ -0,0 +1,605 @@
+//! GDB remote serial protocol stub on COM2
+//!
+//! The stub takes over the machine when a breakpoint, a single step or a
+//! break-in (Ctrl-C) from GDB traps into the kernel, and serves GDB until
+//! it continues. Each process is reported to GDB as a thread, whose id is
+//! the `ProcessId`. Memory is accessed through the page table of the
+//! stopped process.
+//!
+//! Connect with `target remote` to the host end of QEMU's second serial port.
+
+mod packet;
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use x86_64::registers::rflags::RFlags;
+
+use self::packet::*;
+use crate::drivers::uart16550::SerialPort;
+use crate::memory::{physical_to_virtual, translate_active};
+use crate::proc::context::{ProcessContext, ProcessContextValue};
+use crate::proc::manager::get_process_manager;
+
+const MAX_BREAKPOINTS: usize = 32;
+
+/// The `int3` instruction
+const INT3: u8 = 0xcc;
+
+/// Count of registers in a `g` packet
+const REGISTER_COUNT: usize = 24;
+
+/// Why the stub was entered, as a GDB signal number
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+pub enum StopReason {
+    /// Break-in requested by GDB
+    Interrupt = 2,
+    /// Breakpoint or single step
+    Trap = 5,
+}
+
+/// What to do after a packet is handled
+enum Action {
+    Reply,
+    Resume,
+    /// Reply, then resume without the debugger
+    Detach,
+}
+
+#[derive(Clone, Copy)]
+struct Breakpoint {
+    addr: u64,
+    saved: u8,
+}
+
+struct GdbStub {
+    conn: Connection,
+    rx: [u8; PACKET_SIZE],
+    state: StubState,
+}
+
+struct StubState {
+    tx: Response,
+    breakpoints: [Option<Breakpoint>; MAX_BREAKPOINTS],
+    /// thread selected by `Hg`, 0 for the stopped one
+    selected: u16,
+}
+
+static STUB: spin::Once<spin::Mutex<GdbStub>> = spin::Once::new();
+
+/// Set when the stub resumes with a single step
+static STEPPING: AtomicBool = AtomicBool::new(false);
+
+pub fn init() {
+    let mut port = SerialPort::<GDB_IO_PORT>::new();
+
+    if !port.try_init() {
+        info!("COM2 not found, GDB stub disabled.");
+        return;
+    }
+
+    STUB.call_once(|| {
+        spin::Mutex::new(GdbStub {
+            conn: Connection::new(port),
+            rx: [0; PACKET_SIZE],
+            state: StubState {
+                tx: Response::new(),
+                breakpoints: [None; MAX_BREAKPOINTS],
+                selected: 0,
+            },
+        })
+    });
+
+    info!("GDB stub listening on COM2.");
+}
+
+#[inline]
+pub fn enabled() -> bool {
+    STUB.get().is_some()
+}
+
+/// Check if a debug exception was caused by a single step of the stub
+#[inline]
+pub fn is_stepping() -> bool {
+    STEPPING.load(Ordering::Relaxed)
+}
+
+/// Stop in the stub if GDB sent a break-in request on COM2
+pub fn poll_interrupt(context: &mut ProcessContext) {
+    let Some(stub) = STUB.get() else {
+        return;
+    };
+
+    let interrupted = match stub.try_lock() {
+        Some(mut stub) => {
+            let mut interrupted = false;
+            while let Some(byte) = stub.conn.try_read_byte() {
+                interrupted |= byte == INTERRUPT;
+            }
+            interrupted
+        }
+        None => false,
+    };
+
+    if interrupted {
+        handle_exception(StopReason::Interrupt, context);
+    }
+}
+
+/// Stop in the stub until GDB resumes the context
+///
+/// Return false if the stub is disabled or already running.
+pub fn handle_exception(reason: StopReason, context: &mut ProcessContext) -> bool {
+    let Some(stub) = STUB.get() else {
+        return false;
+    };
+
+    let Some(mut stub) = stub.try_lock() else {
+        return false;
+    };
+
+    STEPPING.store(false, Ordering::Relaxed);
+
+    let mut value: ProcessContextValue = **context;
+    value.stack_frame.cpu_flags.remove(RFlags::TRAP_FLAG);
+
+    // rip is after the int3 of a breakpoint set by GDB
+    let rip = value.stack_frame.instruction_pointer.as_u64();
+    if stub.state.breakpoint(rip.wrapping_sub(1)).is_some() {
+        value.stack_frame.instruction_pointer -= 1u64;
+    }
+
+    stub.run(reason, &mut value);
+
+    STEPPING.store(
+        value.stack_frame.cpu_flags.contains(RFlags::TRAP_FLAG),
+        Ordering::Relaxed,
+    );
+    context.as_mut().as_mut_ptr().write(value);
+
+    true
+}
+
+/// Trap into the stub from kernel code
+#[inline(always)]
+pub fn breakpoint() {
+    x86_64::instructions::interrupts::int3();
+}
+
+impl GdbStub {
+    fn run(&mut self, reason: StopReason, value: &mut ProcessContextValue) {
+        self.state.selected = 0;
+        self.state.stop_reply(reason);
+        self.conn.write_packet(self.state.tx.as_bytes());
+
+        loop {
+            let packet = self.conn.read_packet(&mut self.rx);
+
+            self.state.tx.clear();
+            match self.state.handle(packet, reason, value) {
+                Action::Reply => self.conn.write_packet(self.state.tx.as_bytes()),
+                Action::Resume => break,
+                Action::Detach => {
+                    self.conn.write_packet(self.state.tx.as_bytes());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl StubState {
+    fn handle(
+        &mut self,
+        packet: &[u8],
+        reason: StopReason,
+        value: &mut ProcessContextValue,
+    ) -> Action {
+        let Some((&command, args)) = packet.split_first() else {
+            return Action::Reply;
+        };
+
+        match command {
+            b'?' => self.stop_reply(reason),
+            b'g' => self.read_registers(value),
+            b'G' => self.write_registers(args, value),
+            b'p' => self.read_register(args, value),
+            b'P' => self.write_register(args, value),
+            b'm' => self.read_memory(args),
+            b'M' => self.write_memory(args),
+            b'Z' => self.insert_breakpoint(args),
+            b'z' => self.remove_breakpoint(args),
+            b'H' => self.set_thread(args),
+            b'T' => self.thread_alive(args),
+            b'q' => self.query(args),
+            b'c' | b's' => {
+                if let Some(addr) = parse_hex(args) {
+                    value.stack_frame.instruction_pointer = x86_64::VirtAddr::new_truncate(addr);
+                }
+                if command == b's' {
+                    value.stack_frame.cpu_flags.insert(RFlags::TRAP_FLAG);
+                }
+                return Action::Resume;
+            }
+            b'D' | b'k' => {
+                self.remove_all_breakpoints();
+                value.stack_frame.cpu_flags.remove(RFlags::TRAP_FLAG);
+                if command == b'D' {
+                    self.tx.push(b"OK");
+                    return Action::Detach;
+                }
+                return Action::Resume;
+            }
+            // unsupported packets get an empty reply
+            _ => {}
+        }
+
+        Action::Reply
+    }
+
+    fn stop_reply(&mut self, reason: StopReason) {
+        let pid = get_process_manager().current_pid();
+
+        self.tx.clear();
+        let _ = write!(self.tx, "T{:02x}thread:{:x};", reason as u8, pid.0);
+    }
+
+    /// Get a register as (value, size in bytes) in the order of GDB's amd64 `g` packet
+    fn register(value: &ProcessContextValue, index: usize) -> Option<(u64, usize)> {
+        let regs = &value.regs;
+        let frame = &value.stack_frame;
+
+        let reg = match index {
+            0 => regs.rax,
+            1 => regs.rbx,
+            2 => regs.rcx,
+            3 => regs.rdx,
+            4 => regs.rsi,
+            5 => regs.rdi,
+            6 => regs.rbp,
+            7 => frame.stack_pointer.as_u64() as usize,
+            8 => regs.r8,
+            9 => regs.r9,
+            10 => regs.r10,
+            11 => regs.r11,
+            12 => regs.r12,
+            13 => regs.r13,
+            14 => regs.r14,
+            15 => regs.r15,
+            16 => frame.instruction_pointer.as_u64() as usize,
+            17 => return Some((frame.cpu_flags.bits(), 4)),
+            18 => return Some((frame.code_segment.0 as u64, 4)),
+            // ss, ds and es share the data selector
+            19..=21 => return Some((frame.stack_segment.0 as u64, 4)),
+            22 | 23 => return Some((0, 4)),
+            _ => return None,
+        };
+
+        Some((reg as u64, 8))
+    }
+
+    /// Set a register, segment selectors are read-only
+    fn set_register(value: &mut ProcessContextValue, index: usize, reg: u64) -> bool {
+        let regs = &mut value.regs;
+        let frame = &mut value.stack_frame;
+
+        match index {
+            0 => regs.rax = reg as usize,
+            1 => regs.rbx = reg as usize,
+            2 => regs.rcx = reg as usize,
+            3 => regs.rdx = reg as usize,
+            4 => regs.rsi = reg as usize,
+            5 => regs.rdi = reg as usize,
+            6 => regs.rbp = reg as usize,
+            7 => frame.stack_pointer = x86_64::VirtAddr::new_truncate(reg),
+            8 => regs.r8 = reg as usize,
+            9 => regs.r9 = reg as usize,
+            10 => regs.r10 = reg as usize,
+            11 => regs.r11 = reg as usize,
+            12 => regs.r12 = reg as usize,
+            13 => regs.r13 = reg as usize,
+            14 => regs.r14 = reg as usize,
+            15 => regs.r15 = reg as usize,
+            16 => frame.instruction_pointer = x86_64::VirtAddr::new_truncate(reg),
+            17 => frame.cpu_flags = RFlags::from_bits_truncate(reg),
+            18..REGISTER_COUNT => {}
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// The saved context of the thread selected by `Hg`
+    fn selected_context(&self) -> Option<ProcessContextValue> {
+        let mut context = None;
+        get_process_manager().try_for_each(|proc, inner| {
+            if proc.pid().0 == self.selected {
+                context = Some(**inner.context());
+            }
+        });
+        context
+    }
+
+    fn is_selected_current(&self) -> bool {
+        self.selected == 0 || self.selected == get_process_manager().current_pid().0
+    }
+
+    fn read_registers(&mut self, value: &ProcessContextValue) {
+        let value = if self.is_selected_current() {
+            *value
+        } else {
+            match self.selected_context() {
+                Some(context) => context,
+                None => return self.tx.push(b"E01"),
+            }
+        };
+
+        for index in 0..REGISTER_COUNT {
+            if let Some((reg, size)) = Self::register(&value, index) {
+                self.tx.push_hex(&reg.to_le_bytes()[..size]);
+            }
+        }
+    }
+
+    fn write_registers(&mut self, args: &[u8], value: &mut ProcessContextValue) {
+        if !self.is_selected_current() {
+            return self.tx.push(b"E01");
+        }
+
+        let mut buf = [0u8; 8];
+        let mut offset = 0;
+
+        for index in 0..REGISTER_COUNT {
+            let Some((_, size)) = Self::register(value, index) else {
+                break;
+            };
+
+            let Some(hex) = args.get(offset..offset + size * 2) else {
+                break;
+            };
+
+            if decode_hex(hex, &mut buf[..size]).is_none() {
+                return self.tx.push(b"E01");
+            }
+
+            Self::set_register(value, index, u64::from_le_bytes(buf));
+            buf = [0; 8];
+            offset += size * 2;
+        }
+
+        self.tx.push(b"OK");
+    }
+
+    fn read_register(&mut self, args: &[u8], value: &ProcessContextValue) {
+        let index = parse_hex(args).unwrap_or(u64::MAX) as usize;
+
+        let value = if self.is_selected_current() {
+            *value
+        } else {
+            match self.selected_context() {
+                Some(context) => context,
+                None => return self.tx.push(b"E01"),
+            }
+        };
+
+        match Self::register(&value, index) {
+            Some((reg, size)) => self.tx.push_hex(&reg.to_le_bytes()[..size]),
+            None => self.tx.push(b"E01"),
+        }
+    }
+
+    fn write_register(&mut self, args: &[u8], value: &mut ProcessContextValue) {
+        let mut parts = args.splitn(2, |&b| b == b'=');
+        let index = parts.next().and_then(parse_hex);
+        let mut buf = [0u8; 8];
+        let decoded = parts.next().and_then(|hex| decode_hex(hex, &mut buf));
+
+        match (index, decoded) {
+            (Some(index), Some(_)) if self.is_selected_current() => {
+                if Self::set_register(value, index as usize, u64::from_le_bytes(buf)) {
+                    self.tx.push(b"OK");
+                } else {
+                    self.tx.push(b"E01");
+                }
+            }
+            _ => self.tx.push(b"E01"),
+        }
+    }
+
+    fn read_memory(&mut self, args: &[u8]) {
+        let Some((addr, len)) = parse_range(args) else {
+            return self.tx.push(b"E01");
+        };
+
+        // two hex digits per byte
+        let len = len.min((PACKET_SIZE / 2) as u64);
+        let mut buf = [0u8; 64];
+
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(buf.len() as u64) as usize;
+            for (i, byte) in buf[..chunk].iter_mut().enumerate() {
+                match read_byte(addr + done + i as u64) {
+                    Some(value) => *byte = value,
+                    None if done == 0 && i == 0 => return self.tx.push(b"E14"),
+                    // reply with the readable part
+                    None => return self.tx.push_hex(&buf[..i]),
+                }
+            }
+            self.tx.push_hex(&buf[..chunk]);
+            done += chunk as u64;
+        }
+    }
+
+    fn write_memory(&mut self, args: &[u8]) {
+        let mut parts = args.splitn(2, |&b| b == b':');
+        let range = parts.next().and_then(parse_range);
+        let data = parts.next().unwrap_or_default();
+
+        let Some((addr, len)) = range else {
+            return self.tx.push(b"E01");
+        };
+
+        if data.len() as u64 != len * 2 {
+            return self.tx.push(b"E01");
+        }
+
+        for (i, pair) in data.as_chunks::<2>().0.iter().enumerate() {
+            let mut byte = [0u8];
+            if decode_hex(pair, &mut byte).is_none() || !write_byte(addr + i as u64, byte[0]) {
+                return self.tx.push(b"E14");
+            }
+        }
+
+        self.tx.push(b"OK");
+    }
+
+    fn breakpoint(&self, addr: u64) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .position(|bp| bp.is_some_and(|bp| bp.addr == addr))
+    }
+
+    /// `Z0,addr,kind`, only software breakpoints are supported
+    fn insert_breakpoint(&mut self, args: &[u8]) {
+        let Some(addr) = parse_breakpoint(args) else {
+            return;
+        };
+
+        if self.breakpoint(addr).is_some() {
+            return self.tx.push(b"OK");
+        }
+
+        let Some(slot) = self.breakpoints.iter().position(|bp| bp.is_none()) else {
+            return self.tx.push(b"E01");
+        };
+
+        match read_byte(addr) {
+            Some(saved) if write_byte(addr, INT3) => {
+                self.breakpoints[slot] = Some(Breakpoint { addr, saved });
+                self.tx.push(b"OK");
+            }
+            _ => self.tx.push(b"E14"),
+        }
+    }
+
+    fn remove_breakpoint(&mut self, args: &[u8]) {
+        let Some(addr) = parse_breakpoint(args) else {
+            return;
+        };
+
+        if let Some(index) = self.breakpoint(addr)
+            && let Some(bp) = self.breakpoints[index].take()
+        {
+            write_byte(bp.addr, bp.saved);
+        }
+
+        self.tx.push(b"OK");
+    }
+
+    fn remove_all_breakpoints(&mut self) {
+        for bp in self.breakpoints.iter_mut().filter_map(Option::take) {
+            write_byte(bp.addr, bp.saved);
+        }
+    }
+
+    /// `Hg<tid>` selects the thread for registers, `Hc` is accepted as is
+    fn set_thread(&mut self, args: &[u8]) {
+        let Some((&op, tid)) = args.split_first() else {
+            return self.tx.push(b"E01");
+        };
+
+        if op == b'g' {
+            self.selected = match tid {
+                b"-1" | b"0" => 0,
+                tid => match parse_hex(tid) {
+                    Some(pid) if thread_exists(pid as u16) => pid as u16,
+                    _ => return self.tx.push(b"E01"),
+                },
+            };
+        }
+
+        self.tx.push(b"OK");
+    }
+
+    fn thread_alive(&mut self, args: &[u8]) {
+        match parse_hex(args) {
+            Some(pid) if thread_exists(pid as u16) => self.tx.push(b"OK"),
+            _ => self.tx.push(b"E01"),
+        }
+    }
+
+    fn query(&mut self, args: &[u8]) {
+        if args.starts_with(b"Supported") {
+            let _ = write!(self.tx, "PacketSize={:x}", PACKET_SIZE);
+        } else if args == b"Attached" {
+            self.tx.push(b"1");
+        } else if args == b"C" {
+            let pid = get_process_manager().current_pid();
+            let _ = write!(self.tx, "QC{:x}", pid.0);
+        } else if args == b"fThreadInfo" {
+            self.tx.push(b"m");
+            let mut first = true;
+            let tx = &mut self.tx;
+            get_process_manager().try_for_each(|proc, _| {
+                if !first {
+                    tx.push(b",");
+                }
+                let _ = write!(tx, "{:x}", proc.pid().0);
+                first = false;
+            });
+        } else if args == b"sThreadInfo" {
+            self.tx.push(b"l");
+        } else if let Some(tid) = args.strip_prefix(b"ThreadExtraInfo,") {
+            let pid = parse_hex(tid).unwrap_or(0) as u16;
+            let tx = &mut self.tx;
+            get_process_manager().try_for_each(|proc, inner| {
+                if proc.pid().0 == pid {
+                    let mut info = Response::new();
+                    let _ = write!(info, "{} ({:?})", inner.name(), inner.status());
+                    tx.push_hex(info.as_bytes());
+                }
+            });
+        }
+    }
+}
+
+fn thread_exists(pid: u16) -> bool {
+    let mut exists = false;
+    get_process_manager().try_for_each(|proc, _| exists |= proc.pid().0 == pid);
+    exists
+}
+
+/// Parse `addr,len`
+fn parse_range(args: &[u8]) -> Option<(u64, u64)> {
+    let mut parts = args.splitn(2, |&b| b == b',');
+    let addr = parse_hex(parts.next()?)?;
+    let len = parse_hex(parts.next()?)?;
+    Some((addr, len))
+}
+
+/// Parse `0,addr,kind` of a software breakpoint
+fn parse_breakpoint(args: &[u8]) -> Option<u64> {
+    let mut parts = args.split(|&b| b == b',');
+    if parts.next()? != b"0" {
+        return None;
+    }
+    parse_hex(parts.next()?)
+}
+
+/// Read a byte through the physical memory mapping
+fn read_byte(addr: u64) -> Option<u8> {
+    let phys = translate_active(addr)?;
+    Some(unsafe { *(physical_to_virtual(phys.as_u64()) as *const u8) })
+}
+
+/// Write a byte through the physical memory mapping, even on read-only pages
+fn write_byte(addr: u64, byte: u8) -> bool {
+    let Some(phys) = translate_active(addr) else {
+        return false;
+    };
+
+    unsafe { *(physical_to_virtual(phys.as_u64()) as *mut u8) = byte };
+    true
+}
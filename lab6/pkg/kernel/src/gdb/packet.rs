@@ -0,0 +1,178 @@
+use core::fmt;
+
+use crate::drivers::uart16550::SerialPort;
+
+pub const GDB_IO_PORT: u16 = 0x2F8; // COM2
+
+/// Maximum size of a packet, advertised to GDB in `qSupported`
+pub const PACKET_SIZE: usize = 0x1000;
+
+/// Byte sent by GDB to interrupt the target (Ctrl-C)
+pub const INTERRUPT: u8 = 0x03;
+
+/// A GDB remote serial protocol connection
+pub struct Connection {
+    port: SerialPort<GDB_IO_PORT>,
+}
+
+impl Connection {
+    pub fn new(port: SerialPort<GDB_IO_PORT>) -> Self {
+        Self { port }
+    }
+
+    /// Receive a byte without waiting
+    #[inline]
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        self.port.receive()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Some(byte) = self.port.receive() {
+                return byte;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Receive a packet into `buf` and acknowledge it
+    ///
+    /// bytes beyond the size of `buf` are dropped
+    pub fn read_packet<'a>(&mut self, buf: &'a mut [u8]) -> &'a [u8] {
+        loop {
+            while self.read_byte() != b'$' {}
+
+            let mut len = 0;
+            let mut sum = 0u8;
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    b'$' => {
+                        // a new packet, drop the broken one
+                        len = 0;
+                        sum = 0;
+                    }
+                    byte => {
+                        if len < buf.len() {
+                            buf[len] = byte;
+                            len += 1;
+                        }
+                        sum = sum.wrapping_add(byte);
+                    }
+                }
+            }
+
+            let hi = hex_value(self.read_byte());
+            let lo = hex_value(self.read_byte());
+
+            match (hi, lo) {
+                (Some(hi), Some(lo)) if hi << 4 | lo == sum => {
+                    self.port.send(b'+');
+                    return &buf[..len];
+                }
+                _ => self.port.send(b'-'),
+            }
+        }
+    }
+
+    /// Send a packet, until GDB acknowledges it
+    pub fn write_packet(&mut self, data: &[u8]) {
+        let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+        loop {
+            self.port.send(b'$');
+            for &byte in data {
+                self.port.send(byte);
+            }
+            self.port.send(b'#');
+            self.port.send(HEX[(sum >> 4) as usize]);
+            self.port.send(HEX[(sum & 0xf) as usize]);
+
+            loop {
+                match self.read_byte() {
+                    b'+' => return,
+                    b'-' => break,
+                    _ => continue,
+                }
+            }
+        }
+    }
+}
+
+/// Reply of a packet
+pub struct Response {
+    buf: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+impl Response {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        let len = data.len().min(PACKET_SIZE - self.len);
+        self.buf[self.len..self.len + len].copy_from_slice(&data[..len]);
+        self.len += len;
+    }
+
+    /// Push bytes as hex digits
+    pub fn push_hex(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.push(&[HEX[(byte >> 4) as usize], HEX[(byte & 0xf) as usize]]);
+        }
+    }
+}
+
+impl fmt::Write for Response {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+pub fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parse a hex number, as used for addresses and lengths
+pub fn parse_hex(data: &[u8]) -> Option<u64> {
+    if data.is_empty() || data.len() > 16 {
+        return None;
+    }
+
+    data.iter().try_fold(0u64, |value, &byte| {
+        Some(value << 4 | hex_value(byte)? as u64)
+    })
+}
+
+/// Decode hex digits into `buf`, return the count of decoded bytes
+pub fn decode_hex(data: &[u8], buf: &mut [u8]) -> Option<usize> {
+    if data.len() % 2 != 0 || data.len() / 2 > buf.len() {
+        return None;
+    }
+
+    for (i, [hi, lo]) in data.as_chunks::<2>().0.iter().enumerate() {
+        buf[i] = hex_value(*hi)? << 4 | hex_value(*lo)?;
+    }
+
+    Some(data.len() / 2)
+}
@@ -43,7 +43,7 @@ impl IoApic {
         // Mark all interrupts edge-triggered, active high, disabled,
         // and not routed to any CPUs.
         for i in 0..=self.maxintr() {
-            self.write_irq(i, RedirectionEntry::DISABLED, 0);
+            self.write_irq(i, i, RedirectionEntry::DISABLED, 0);
         }
     }
 
@@ -61,21 +61,32 @@ impl IoApic {
         }
     }
 
-    fn write_irq(&mut self, irq: u8, flags: RedirectionEntry, dest: u8) {
-        self.write(0x10 + 2 * irq, (32 + irq) as u32 | flags.bits());
-        self.write(0x10 + 2 * irq + 1, (dest as u32) << 24);
+    /// Route `pin` to the vector of `irq`
+    fn write_irq(&mut self, pin: u8, irq: u8, flags: RedirectionEntry, dest: u8) {
+        self.write(0x10 + 2 * pin, (32 + irq) as u32 | flags.bits());
+        self.write(0x10 + 2 * pin + 1, (dest as u32) << 24);
     }
 
     pub fn enable(&mut self, irq: u8, cpuid: u8) {
         // Mark interrupt edge-triggered, active high,
         // enabled, and routed to the given cpuid,
         // which happens to be that cpu's APIC ID.
-        self.write_irq(irq, RedirectionEntry::NONE, cpuid);
-        trace!("Enable IOApic: IRQ={}, CPU={}", irq, cpuid);
+        self.enable_pin(irq, irq, false, false, cpuid);
+    }
+
+    /// Enable `pin` as `irq`, with the polarity and trigger mode
+    /// of an interrupt source override
+    pub fn enable_pin(&mut self, pin: u8, irq: u8, active_low: bool, level: bool, cpuid: u8) {
+        let mut flags = RedirectionEntry::NONE;
+        flags.set(RedirectionEntry::ACTIVELOW, active_low);
+        flags.set(RedirectionEntry::LEVEL, level);
+
+        self.write_irq(pin, irq, flags, cpuid);
+        trace!("Enable IOApic: IRQ={}, PIN={}, CPU={}", irq, pin, cpuid);
     }
 
     pub fn disable(&mut self, irq: u8, cpuid: u8) {
-        self.write_irq(irq, RedirectionEntry::DISABLED, cpuid);
+        self.write_irq(irq, irq, RedirectionEntry::DISABLED, cpuid);
     }
 
     pub fn id(&mut self) -> u8 {
@@ -1,4 +1,6 @@
 use super::LocalApic;
+use crate::drivers::pit;
+use crate::interrupt::clock;
 use crate::interrupt::consts::{Interrupts, Irq};
 use bit_field::BitField;
 use core::fmt::{Debug, Error, Formatter};
@@ -8,6 +10,31 @@ use x86::cpuid::CpuId;
 /// Default physical address of xAPIC
 pub const LAPIC_ADDR: u64 = 0xFEE00000;
 
+/// Frequency of the APIC timer before the divider
+///
+/// The bus frequency emulated by QEMU, only used if the calibration
+/// against the PIT fails.
+const APIC_BUS_HZ: u64 = 1_000_000_000;
+
+/// Divider of the APIC timer, set in TDCR
+const TIMER_DIVIDE: u64 = 64;
+
+/// Length of the calibration against the PIT
+const CALIBRATE_MS: u64 = 10;
+
+const LVT_TIMER: u32 = 0x320;
+const TIMER_INIT_COUNT: u32 = 0x380;
+const TIMER_CURRENT_COUNT: u32 = 0x390;
+const TIMER_DIVIDE_CONFIG: u32 = 0x3E0;
+
+bitflags! {
+    struct Lvtt: u32 {
+        const PERIODIC = 1 << 17;
+        const MASKED = 1 << 16;
+        const VECTOR = Interrupts::IrqBase as u32 + Irq::Timer as u32;
+    }
+}
+
 pub struct XApic {
     addr: u64,
 }
@@ -27,6 +54,36 @@ impl XApic {
             self.read(0x20);
         }
     }
+
+    /// Start the timer with the initial count, 0 stops it
+    pub fn set_timer(&mut self, count: u32) {
+        unsafe { self.write(TIMER_INIT_COUNT, count) };
+    }
+
+    /// Count down the masked timer and the TSC while the PIT waits
+    ///
+    /// Returns the frequencies of the timer after the divider and of the
+    /// TSC, which is 0 if it did not count.
+    unsafe fn calibrate(&mut self) -> Option<(u64, u64)> {
+        unsafe {
+            self.write(LVT_TIMER, (Lvtt::MASKED | Lvtt::VECTOR).bits());
+            self.write(TIMER_INIT_COUNT, u32::MAX);
+
+            let tsc = clock::read_tsc();
+            let waited = pit::wait_ms(CALIBRATE_MS);
+            let counted = u32::MAX - self.read(TIMER_CURRENT_COUNT);
+            let cycles = clock::read_tsc().saturating_sub(tsc);
+
+            self.write(TIMER_INIT_COUNT, 0);
+
+            (waited && counted > 0).then(|| {
+                (
+                    counted as u64 * 1000 / CALIBRATE_MS,
+                    cycles * 1000 / CALIBRATE_MS,
+                )
+            })
+        }
+    }
 }
 
 impl LocalApic for XApic {
@@ -52,17 +109,6 @@ impl LocalApic for XApic {
             let spiv_value = Spiv::ENABLE | Spiv::VECTOR;
             self.write(0xF0, spiv_value.bits());
 
-            // FIXME: The timer repeatedly counts down at bus frequency
-            bitflags! {
-                struct Lvtt: u32 {
-                    const PERIODIC = 1 << 17;
-                    const MASKED = 1 << 16;
-                    const VECTOR = Interrupts::IrqBase as u32 + Irq::Timer as u32;
-                }
-            }
-            let lvtt_value = Lvtt::PERIODIC | Lvtt::VECTOR & !Lvtt::MASKED;
-            self.write(0x320, lvtt_value.bits());
-
             bitflags! {
                 struct Tdcr: u32 {
                     const DIVIDE_1 = 0b1011;
@@ -75,14 +121,25 @@ impl LocalApic for XApic {
                     const DIVIDE_128 = 0b1010;
                 }
             }
-            self.write(0x3E0, Tdcr::DIVIDE_64.bits());
-
-            bitflags! {
-                struct Ticr: u32 {
-                    const INIT = 0x2000;
-                }
-            }
-            self.write(0x380, Ticr::INIT.bits());
+            self.write(TIMER_DIVIDE_CONFIG, Tdcr::DIVIDE_64.bits());
+
+            // the tick rate of the timer and the TSC depend on the host
+            let (timer_hz, tsc_hz) = self.calibrate().unwrap_or_else(|| {
+                warn!("Failed to calibrate the APIC timer with the PIT.");
+                (APIC_BUS_HZ / TIMER_DIVIDE, 0)
+            });
+            clock::init(timer_hz, tsc_hz);
+
+            // the one-shot timer is armed again by each tick
+            let lvtt_value = if clock::is_oneshot() {
+                Lvtt::VECTOR
+            } else {
+                Lvtt::PERIODIC | Lvtt::VECTOR
+            };
+            self.write(LVT_TIMER, lvtt_value.bits());
+
+            // initial count for the timer to fire at HZ
+            self.set_timer(clock::tick_count());
 
             // FIXME: Disable logical interrupt lines (LINT0, LINT1)
             bitflags! {
@@ -1,10 +1,90 @@
 use super::consts::*;
 use crate::as_handler;
 use crate::memory::gdt::TIMER_IST_INDEX;
+use crate::proc;
 use crate::proc::context;
 use crate::proc::switch;
+use crate::utils::params::{Param, ParamValue};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+use x86_64::instructions::interrupts;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 
+/// Frequency of the timer interrupt
+pub static HZ: Param<u64> = Param::new("hz", 1000);
+
+/// Mode of the APIC timer, see `TimerMode`
+pub static TIMER: Param<TimerMode> = Param::new("timer", TimerMode::OneShot);
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Clock interrupts handled
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Counts of the APIC timer per second, after the divider
+static TIMER_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// Frequency of the TSC, 0 if it is not calibrated
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// TSC when the timer was enabled
+static BOOT_TSC: AtomicU64 = AtomicU64::new(0);
+
+static ONESHOT: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerMode {
+    /// Interrupt at `HZ` all the time
+    Periodic,
+    /// Arm the timer for every tick, and skip the ticks while idle
+    OneShot,
+}
+
+impl ParamValue for TimerMode {
+    fn parse(value: &'static str) -> Option<Self> {
+        match value {
+            "periodic" => Some(Self::Periodic),
+            "oneshot" => Some(Self::OneShot),
+            _ => None,
+        }
+    }
+}
+
+/// Set the frequencies measured by the APIC timer calibration
+///
+/// `tsc_hz` is 0 if the TSC is unusable, then ticks are counted by the
+/// interrupts and the timer can only be periodic.
+pub fn init(timer_hz: u64, tsc_hz: u64) {
+    BOOT_TSC.store(read_tsc(), Ordering::Relaxed);
+    TIMER_HZ.store(timer_hz, Ordering::Relaxed);
+    TSC_HZ.store(tsc_hz, Ordering::Relaxed);
+
+    let oneshot = TIMER.get() == TimerMode::OneShot && tsc_hz > 0;
+    if TIMER.get() == TimerMode::OneShot && !oneshot {
+        warn!("TSC is not calibrated, falling back to the periodic timer.");
+    }
+    ONESHOT.store(oneshot, Ordering::Relaxed);
+
+    info!(
+        "APIC timer: {} kHz, TSC: {} MHz, {} Hz {}",
+        timer_hz / 1000,
+        tsc_hz / 1_000_000,
+        hz(),
+        if oneshot { "one-shot" } else { "periodic" }
+    );
+}
+
+/// If the timer is armed for each tick
+#[inline]
+pub fn is_oneshot() -> bool {
+    ONESHOT.load(Ordering::Relaxed)
+}
+
+/// Initial count of the APIC timer for one tick
+pub fn tick_count() -> u32 {
+    (TIMER_HZ.load(Ordering::Relaxed) / hz()).clamp(1, u32::MAX as u64) as u32
+}
+
 pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
     idt[Interrupts::IrqBase as u8 + Irq::Timer as u8]
         .set_handler_fn(clock_handler)
@@ -12,8 +92,73 @@ pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
 }
 
 pub extern "C" fn clock(mut context: context::ProcessContext) {
+    COUNTER.fetch_add(1, Ordering::Relaxed);
+    if is_oneshot() {
+        super::lapic().set_timer(tick_count());
+    }
     switch(&mut context);
     super::ack();
 }
 
 as_handler!(clock);
+
+/// Halt until an interrupt, or until the tick `deadline` if given
+///
+/// With the one-shot timer and no process ready to run, the timer is
+/// armed for the deadline instead of the next tick, so an idle CPU is
+/// not woken up at `HZ` for nothing.
+pub fn idle(deadline: Option<u64>) {
+    interrupts::disable();
+
+    let tickless = is_oneshot() && !proc::has_ready();
+    if tickless {
+        let ticks = match deadline {
+            Some(deadline) => deadline.saturating_sub(read_counter()).max(1),
+            None => u64::MAX,
+        };
+        let count = ticks.saturating_mul(tick_count() as u64);
+        super::lapic().set_timer(count.min(u32::MAX as u64) as u32);
+    }
+
+    interrupts::enable_and_hlt();
+
+    if tickless {
+        // woken up by another interrupt, which may have made a process ready
+        interrupts::without_interrupts(|| super::lapic().set_timer(tick_count()));
+    }
+}
+
+/// Get the clock ticks since the timer was enabled
+///
+/// Once the TSC is calibrated, ticks are derived from it, as the timer
+/// does not interrupt at every tick while idle.
+#[inline]
+pub fn read_counter() -> u64 {
+    match TSC_HZ.load(Ordering::Relaxed) {
+        0 => COUNTER.load(Ordering::Relaxed),
+        tsc_hz => (elapsed_tsc() as u128 * hz() as u128 / tsc_hz as u128) as u64,
+    }
+}
+
+/// Time since the timer was enabled, in TSC resolution if calibrated
+pub fn uptime() -> Duration {
+    let nanos = match TSC_HZ.load(Ordering::Relaxed) {
+        0 => COUNTER.load(Ordering::Relaxed) as u128 * NANOS_PER_SEC / hz() as u128,
+        tsc_hz => elapsed_tsc() as u128 * NANOS_PER_SEC / tsc_hz as u128,
+    };
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Read the time stamp counter of current processor
+#[inline]
+pub fn read_tsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+fn elapsed_tsc() -> u64 {
+    read_tsc().saturating_sub(BOOT_TSC.load(Ordering::Relaxed))
+}
+
+fn hz() -> u64 {
+    HZ.get().max(1)
+}
@@ -1,4 +1,8 @@
+use crate::gdb::{self, StopReason};
 use crate::memory::*;
+use crate::proc::{self, ProcessContext};
+use crate::utils::backtrace;
+use syscall_def::signal::Signal;
 use x86_64::VirtAddr;
 use x86_64::registers::control::Cr2;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
@@ -21,7 +25,9 @@ pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
     idt.stack_segment_fault
         .set_handler_fn(stack_segment_fault_handler);
     idt.debug.set_handler_fn(debug_handler);
-    idt.breakpoint.set_handler_fn(breakpoint_handler);
+    idt.breakpoint
+        .set_handler_fn(breakpoint_handler)
+        .set_privilege_level(x86_64::PrivilegeLevel::Ring3);
     idt.overflow.set_handler_fn(overflow_handler);
     idt.bound_range_exceeded
         .set_handler_fn(bound_range_exceeded_handler);
@@ -39,10 +45,25 @@ pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
     // see: https://wiki.osdev.org/Exceptions
 }
 
-pub extern "x86-interrupt" fn divide_error_handler(stack_frame: InterruptStackFrame) {
-    panic!("EXCEPTION: DIVIDE ERROR\n\n{:#?}", stack_frame);
+// faults raised in user mode kill the current process instead of the kernel
+
+/// Make the panic backtrace start at the faulting kernel context
+fn set_fault(context: &ProcessContext) {
+    let rip = context.stack_frame.instruction_pointer.as_u64();
+    backtrace::set_fault(rip, context.regs.rbp as u64);
+}
+
+pub extern "C" fn divide_error(mut context: ProcessContext) {
+    if !context.is_user() {
+        set_fault(&context);
+        panic!("EXCEPTION: DIVIDE ERROR\n\n{:#?}", context);
+    }
+
+    proc::kill_by_signal(Signal::Fpe, format_args!("divide error"), &mut context);
 }
 
+as_handler!(divide_error);
+
 pub extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
@@ -53,15 +74,25 @@ pub extern "x86-interrupt" fn double_fault_handler(
     );
 }
 
-pub extern "x86-interrupt" fn page_fault_handler(
-    stack_frame: InterruptStackFrame,
-    err_code: PageFaultErrorCode,
-) {
+pub extern "C" fn page_fault(mut context: ProcessContext, err_code: u64) {
+    let err_code = PageFaultErrorCode::from_bits_truncate(err_code);
     let fault_addr = Cr2::read().unwrap();
-    if !crate::proc::handle_page_fault(fault_addr, err_code) {
+    if !proc::handle_page_fault(fault_addr, err_code) {
+        if context.is_user() {
+            let kind = if proc::is_stack_overflow(fault_addr) {
+                "stack overflow"
+            } else {
+                "page fault"
+            };
+            let reason = format_args!("{} at {:#x} ({:?})", kind, fault_addr, err_code);
+            proc::kill_by_signal(Signal::Segv, reason, &mut context);
+            return;
+        }
+
+        set_fault(&context);
         warn!(
             "EXCEPTION: PAGE FAULT, ERROR_CODE: {:?}\n\nTrying to access: {:#x}\n{:#?}",
-            err_code, fault_addr, stack_frame
+            err_code, fault_addr, context
         );
         // FIXME: print info about which process causes page fault?
         use crate::proc::manager::get_process_manager;
@@ -72,20 +103,35 @@ pub extern "x86-interrupt" fn page_fault_handler(
         panic!("Cannot handle page fault!");
     }
 }
-pub extern "x86-interrupt" fn general_protection_handler(
-    stack_frame: InterruptStackFrame,
-    error_code: u64,
-) {
-    panic!(
-        "EXCEPTION: GENERAL PROTECTION FAULT, ERROR_CODE: 0x{:016x}\n\n{:#?}",
-        error_code, stack_frame
-    );
+
+as_handler_with_err!(page_fault, PageFaultErrorCode);
+
+pub extern "C" fn general_protection(mut context: ProcessContext, error_code: u64) {
+    if !context.is_user() {
+        set_fault(&context);
+        panic!(
+            "EXCEPTION: GENERAL PROTECTION FAULT, ERROR_CODE: 0x{:016x}\n\n{:#?}",
+            error_code, context
+        );
+    }
+
+    let reason = format_args!("general protection fault ({:#x})", error_code);
+    proc::kill_by_signal(Signal::Segv, reason, &mut context);
 }
 
-pub extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
-    panic!("EXCEPTION: INVALID OPCODE\n\n{:#?}", stack_frame);
+as_handler_with_err!(general_protection, u64);
+
+pub extern "C" fn invalid_opcode(mut context: ProcessContext) {
+    if !context.is_user() {
+        set_fault(&context);
+        panic!("EXCEPTION: INVALID OPCODE\n\n{:#?}", context);
+    }
+
+    proc::kill_by_signal(Signal::Ill, format_args!("invalid opcode"), &mut context);
 }
 
+as_handler!(invalid_opcode);
+
 pub extern "x86-interrupt" fn invalid_tss_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
@@ -116,12 +162,34 @@ pub extern "x86-interrupt" fn stack_segment_fault_handler(
     );
 }
 
-pub extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
-    panic!("EXCEPTION: DEBUG\n\n{:#?}", stack_frame);
+pub extern "C" fn debug(mut context: ProcessContext) {
+    if gdb::is_stepping() && gdb::handle_exception(StopReason::Trap, &mut context) {
+        return;
+    }
+
+    trap(&mut context, "DEBUG");
+}
+
+as_handler!(debug);
+
+pub extern "C" fn breakpoint(mut context: ProcessContext) {
+    if gdb::handle_exception(StopReason::Trap, &mut context) {
+        return;
+    }
+
+    trap(&mut context, "BREAKPOINT");
 }
 
-pub extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    panic!("EXCEPTION: BREAKPOINT\n\n{:#?}", stack_frame);
+as_handler!(breakpoint);
+
+/// A trap without the GDB stub kills a user process
+fn trap(context: &mut ProcessContext, name: &str) {
+    if !context.is_user() {
+        set_fault(context);
+        panic!("EXCEPTION: {}\n\n{:#?}", name, context);
+    }
+
+    proc::kill_by_signal(Signal::Trap, format_args!("trace/breakpoint trap"), context);
 }
 
 pub extern "x86-interrupt" fn overflow_handler(stack_frame: InterruptStackFrame) {
@@ -4,13 +4,21 @@ mod consts;
 mod exceptions;
 mod serial;
 
+use crate::drivers::acpi;
 use crate::memory::physical_to_virtual;
 use apic::*;
 
+pub use apic::{IOAPIC_ADDR, LAPIC_ADDR};
+
 use consts::Irq;
+use x86_64::instructions::port::Port;
 use x86_64::structures::idt::InterruptDescriptorTable;
 pub mod syscall;
 
+/// Data ports of the master and slave 8259 PICs, which hold their masks
+const PIC1_DATA: u16 = 0x21;
+const PIC2_DATA: u16 = 0xA1;
+
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
@@ -32,23 +40,50 @@ pub fn init() {
     if !XApic::support() {
         panic!("xAPIC is not supported!");
     }
-    let mut lapic = unsafe { XApic::new(physical_to_virtual(LAPIC_ADDR)) };
-    lapic.cpu_init();
+    if acpi::madt().legacy_pic {
+        mask_legacy_pic();
+    }
+    lapic().cpu_init();
 
     // FIXME: enable serial irq with IO APIC (use enable_irq)
     enable_irq(Irq::Serial0 as u8, 0); // enable IRQ4 (Serial0) for CPU0
+    if crate::gdb::enabled() {
+        enable_irq(Irq::Serial1 as u8, 0); // IRQ3 (Serial1) for GDB break-in
+    }
 
     info!("Interrupts Initialized.");
 }
 
 #[inline(always)]
 pub fn enable_irq(irq: u8, cpuid: u8) {
-    let mut ioapic = unsafe { IoApic::new(physical_to_virtual(IOAPIC_ADDR)) };
-    ioapic.enable(irq, cpuid);
+    // ISA IRQs may be routed to another pin by the firmware
+    let route = acpi::madt().route(irq);
+    let Some(info) = acpi::madt().ioapic_for(route.gsi) else {
+        warn!("No IOAPIC for IRQ {} (GSI {})", irq, route.gsi);
+        return;
+    };
+
+    let mut ioapic = unsafe { IoApic::new(physical_to_virtual(info.addr)) };
+    let pin = (route.gsi - info.gsi_base) as u8;
+    ioapic.enable_pin(pin, irq, route.active_low, route.level, cpuid);
+}
+
+/// Mask all IRQs of the 8259 PICs, the APICs deliver them instead
+fn mask_legacy_pic() {
+    unsafe {
+        Port::<u8>::new(PIC1_DATA).write(0xff);
+        Port::<u8>::new(PIC2_DATA).write(0xff);
+    }
+    debug!("Legacy 8259 PICs masked.");
 }
 
 #[inline(always)]
 pub fn ack() {
-    let mut lapic = unsafe { XApic::new(physical_to_virtual(LAPIC_ADDR)) };
-    lapic.eoi();
+    lapic().eoi();
+}
+
+/// The local APIC of the current CPU
+#[inline(always)]
+fn lapic() -> XApic {
+    unsafe { XApic::new(physical_to_virtual(acpi::madt().lapic_addr)) }
 }
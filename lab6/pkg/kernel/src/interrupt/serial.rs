@@ -1,13 +1,24 @@
 use super::consts::*;
 use crate::drivers::input;
 use crate::drivers::serial;
+use crate::gdb;
+use crate::proc::ProcessContext;
 use x86_64::structures::idt::InterruptDescriptorTable;
 use x86_64::structures::idt::InterruptStackFrame;
 
 pub unsafe fn register_idt(idt: &mut InterruptDescriptorTable) {
     idt[Interrupts::IrqBase as u8 + Irq::Serial0 as u8].set_handler_fn(serial_handler);
+    idt[Interrupts::IrqBase as u8 + Irq::Serial1 as u8].set_handler_fn(gdb_serial_handler);
 }
 
+/// COM2 is only read for break-in requests from GDB
+pub extern "C" fn gdb_serial(mut context: ProcessContext) {
+    super::ack();
+    gdb::poll_interrupt(&mut context);
+}
+
+as_handler!(gdb_serial);
+
 pub extern "x86-interrupt" fn serial_handler(_st: InterruptStackFrame) {
     receive();
     super::ack();
@@ -1,3 +1,4 @@
+use crate::proc;
 use crate::{memory::gdt, proc::*};
 use alloc::format;
 use x86::bits64::syscall;
@@ -7,6 +8,7 @@ use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 use syscall_def::Syscall;
 
 mod service;
+mod trace;
 use super::consts;
 
 // FIXME: write syscall service handler in `service.rs`
@@ -43,8 +45,12 @@ pub fn dispatcher(context: &mut ProcessContext) {
         context.regs.rdx,
     );
 
-    // NOTE: you may want to trace syscall arguments
-    // trace!("{}", args);
+    proc::enter_kernel();
+
+    // syscalls of traced processes are recorded after dispatching
+    let pid = proc::get_current_pid();
+    let traced = proc::is_traced();
+    let tick = super::clock::read_counter();
 
     match args.syscall {
         // fd: arg0 as u8, buf: &[u8] (ptr: arg1 as *const u8, len: arg2)
@@ -57,6 +63,10 @@ pub fn dispatcher(context: &mut ProcessContext) {
 
         // path: &str (ptr: arg0 as *const u8, len: arg1) -> pid: u16
         Syscall::Spawn => context.set_rax(spawn_process(&args)),
+        // path: &str (ptr: arg0 as *const u8, len: arg1)
+        Syscall::Chdir => context.set_rax(sys_chdir(&args)),
+        // buf: &mut [u8] (ptr: arg0 as *mut u8, len: arg1) -> len: usize
+        Syscall::Getcwd => context.set_rax(sys_getcwd(&args)),
         // ret: arg0 as isize
         Syscall::Exit => exit_process(&args, context),
         // pid: arg0 as u16 -> status: isize
@@ -67,6 +77,14 @@ pub fn dispatcher(context: &mut ProcessContext) {
             list_process();
             context.set_rax(0)
         }
+        // None -> terminating: bool
+        Syscall::Terminating => context.set_rax(sys_terminating()),
+        // buf: &mut [u8] (ptr: arg0, len: arg1) -> len: usize
+        Syscall::GetArgs => context.set_rax(sys_get_args(&args)),
+        // buf: &mut [ProcessInfo] (ptr: arg0, len: arg1) -> count: usize
+        Syscall::ListProcess => context.set_rax(sys_list_process(&args)),
+        // buf: &mut [AppInfo] (ptr: arg0, len: arg1) -> count: usize
+        Syscall::ListAppInfo => context.set_rax(sys_list_app_info(&args)),
         // None
         Syscall::ListApp => {
             list_apps();
@@ -79,6 +97,22 @@ pub fn dispatcher(context: &mut ProcessContext) {
         Syscall::Sem => {
             sys_sem(&args, context);
         }
+        // resource: arg0 as Rlimit -> limit: usize
+        Syscall::GetRlimit => context.set_rax(sys_get_rlimit(&args)),
+        // resource: arg0 as Rlimit, limit: arg1
+        Syscall::SetRlimit => context.set_rax(sys_set_rlimit(&args)),
+        // pid: arg0 as u16 (0 for current), times: arg1 as *mut ProcessTimes
+        Syscall::Times => context.set_rax(sys_times(&args)),
+        // op: arg0 (0: set, 1: read)
+        // set: pid: arg1 as u16 (0 for current), enable: arg2 != 0
+        // read: buf: &mut [TraceRecord] (ptr: arg1, len: arg2) -> count: usize
+        Syscall::Trace => context.set_rax(sys_trace(&args)),
+        // action: arg0 as SyslogAction
+        // read: buf: &mut [u8] (ptr: arg1, len: arg2) -> count: usize
+        // console level: level: arg1, level: spec: &str (ptr: arg1, len: arg2)
+        Syscall::Syslog => context.set_rax(sys_syslog(&args)),
+        // mode: arg0 as RebootMode, only for init
+        Syscall::Reboot => context.set_rax(sys_reboot(&args)),
 
         // ----------------------------------------------------
         // NOTE: following syscall examples are implemented
@@ -91,6 +125,18 @@ pub fn dispatcher(context: &mut ProcessContext) {
         // Unknown
         Syscall::Unknown => warn!("Unhandled syscall: {:x?}", context.regs.rax),
     }
+
+    // the syscall may block or exit the caller and switch to another process
+    let returned = proc::get_current_pid() == pid;
+
+    if traced {
+        trace::record(pid as u16, tick, &args, context, returned);
+    }
+
+    // a caller switched out was charged by `switch_next`
+    if returned {
+        proc::leave_kernel();
+    }
 }
 
 impl SyscallArgs {
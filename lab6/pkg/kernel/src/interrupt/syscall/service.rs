@@ -7,6 +7,11 @@ use crate::proc::*;
 use crate::utils::*;
 
 use super::SyscallArgs;
+use syscall_def::info::{AppInfo, ProcessInfo, ProcessTimes};
+use syscall_def::limit::{RLIM_INFINITY, Rlimit};
+use syscall_def::power::RebootMode;
+use syscall_def::syslog::SyslogAction;
+use syscall_def::trace::TraceRecord;
 
 pub fn spawn_process(args: &SyscallArgs) -> usize {
     // 从参数获取应用程序名称
@@ -25,6 +30,33 @@ pub fn spawn_process(args: &SyscallArgs) -> usize {
     }
 }
 
+pub fn sys_chdir(args: &SyscallArgs) -> usize {
+    let path = unsafe {
+        let ptr = args.arg0 as *const u8;
+        let len = args.arg1;
+        core::slice::from_raw_parts(ptr, len)
+    };
+
+    match core::str::from_utf8(path) {
+        Ok(path) if proc::chdir(path) => 0,
+        _ => usize::MAX,
+    }
+}
+
+pub fn sys_getcwd(args: &SyscallArgs) -> usize {
+    let buf = unsafe {
+        let ptr = args.arg0 as *mut u8;
+        let len = args.arg1;
+        core::slice::from_raw_parts_mut(ptr, len)
+    };
+
+    let cwd = proc::cwd();
+    let len = cwd.len().min(buf.len());
+    buf[..len].copy_from_slice(&cwd.as_bytes()[..len]);
+
+    cwd.len()
+}
+
 pub fn sys_write(args: &SyscallArgs) -> usize {
     // 获取文件描述符和缓冲区
     let fd = args.arg0 as u8;
@@ -59,11 +91,41 @@ pub fn list_process() {
     proc::print_process_list();
 }
 
+/// The length of the arguments may exceed the length of the buffer
+pub fn sys_get_args(args: &SyscallArgs) -> usize {
+    let buf = unsafe { core::slice::from_raw_parts_mut(args.arg0 as *mut u8, args.arg1) };
+    proc::args(buf)
+}
+
+pub fn sys_list_process(args: &SyscallArgs) -> usize {
+    let buf = unsafe {
+        let ptr = args.arg0 as *mut ProcessInfo;
+        let len = args.arg1;
+        core::slice::from_raw_parts_mut(ptr, len)
+    };
+
+    proc::list_process_info(buf)
+}
+
+pub fn sys_list_app_info(args: &SyscallArgs) -> usize {
+    let buf = unsafe {
+        let ptr = args.arg0 as *mut AppInfo;
+        let len = args.arg1;
+        core::slice::from_raw_parts_mut(ptr, len)
+    };
+
+    proc::list_app_info(buf)
+}
+
 pub fn sys_get_current_pid() -> usize {
     // 获取当前进程ID
     proc::get_current_pid() as usize
 }
 
+pub fn sys_terminating() -> usize {
+    proc::is_terminating() as usize
+}
+
 pub fn sys_wait_pid(args: &SyscallArgs, context: &mut ProcessContext) {
     proc::wait_pid(ProcessId(args.arg0 as u16), context);
 }
@@ -75,7 +137,7 @@ pub fn list_apps() {
 pub fn sys_allocate(args: &SyscallArgs) -> usize {
     let layout = unsafe { (args.arg0 as *const Layout).as_ref().unwrap() };
 
-    if layout.size() == 0 {
+    if layout.size() == 0 || !proc::charge_heap(layout.size()) {
         return 0;
     }
 
@@ -85,7 +147,10 @@ pub fn sys_allocate(args: &SyscallArgs) -> usize {
 
     match ret {
         Ok(ptr) => ptr.as_ptr() as usize,
-        Err(_) => 0,
+        Err(_) => {
+            proc::uncharge_heap(layout.size());
+            0
+        }
     }
 }
 
@@ -103,7 +168,33 @@ pub fn sys_deallocate(args: &SyscallArgs) {
             .lock()
             .deallocate(core::ptr::NonNull::new_unchecked(ptr), *layout);
     }
+
+    proc::uncharge_heap(layout.size());
+}
+pub fn sys_times(args: &SyscallArgs) -> usize {
+    let Some(times) = proc::times(ProcessId(args.arg0 as u16)) else {
+        return usize::MAX;
+    };
+
+    unsafe { *(args.arg1 as *mut ProcessTimes) = times };
+    0
+}
+
+/// Unknown resources are unlimited
+pub fn sys_get_rlimit(args: &SyscallArgs) -> usize {
+    match Rlimit::try_from(args.arg0) {
+        Ok(res) => proc::rlimit(res),
+        Err(_) => RLIM_INFINITY,
+    }
+}
+
+pub fn sys_set_rlimit(args: &SyscallArgs) -> usize {
+    match Rlimit::try_from(args.arg0) {
+        Ok(res) if proc::set_rlimit(res, args.arg1) => 0,
+        _ => usize::MAX,
+    }
 }
+
 pub fn sys_fork(context: &mut ProcessContext) {
     proc::fork(context);
 }
@@ -118,6 +209,100 @@ pub fn sys_sem(args: &SyscallArgs, context: &mut ProcessContext) {
     }
 }
 
+pub fn sys_trace(args: &SyscallArgs) -> usize {
+    match args.arg0 {
+        0 => {
+            let pid = ProcessId(args.arg1 as u16);
+            if proc::set_traced(pid, args.arg2 != 0) {
+                0
+            } else {
+                usize::MAX
+            }
+        }
+        1 => {
+            let buf = unsafe {
+                let ptr = args.arg1 as *mut TraceRecord;
+                let len = args.arg2;
+                core::slice::from_raw_parts_mut(ptr, len)
+            };
+            super::trace::drain(buf)
+        }
+        _ => usize::MAX,
+    }
+}
+
+pub fn sys_syslog(args: &SyscallArgs) -> usize {
+    let Ok(action) = SyslogAction::try_from(args.arg0) else {
+        return usize::MAX;
+    };
+
+    // clearing the log and setting levels affect the whole system
+    let pid = ProcessId(proc::get_current_pid() as u16);
+    let permitted = pid == KERNEL_PID || proc::init::is_init_or_descendant(pid);
+    let action = match action {
+        SyslogAction::Read | SyslogAction::Size => action,
+        _ if permitted => action,
+        SyslogAction::ReadClear => {
+            warn!("Process #{} is not allowed to clear the kernel log.", pid);
+            SyslogAction::Read
+        }
+        _ => {
+            warn!(
+                "Process #{} is not allowed to {:?} the kernel log.",
+                pid, action
+            );
+            return usize::MAX;
+        }
+    };
+
+    match action {
+        SyslogAction::Read | SyslogAction::ReadClear => {
+            let buf = unsafe { core::slice::from_raw_parts_mut(args.arg1 as *mut u8, args.arg2) };
+            logger::read(buf, action == SyslogAction::ReadClear)
+        }
+        SyslogAction::Clear => {
+            logger::clear();
+            0
+        }
+        SyslogAction::Size => logger::size(),
+        SyslogAction::ConsoleLevel => match logger::level_from_usize(args.arg1) {
+            Some(level) => {
+                logger::set_console_level(level);
+                0
+            }
+            None => usize::MAX,
+        },
+        SyslogAction::Level => {
+            let spec = unsafe { core::slice::from_raw_parts(args.arg1 as *const u8, args.arg2) };
+            match core::str::from_utf8(spec) {
+                Ok(spec) if logger::set_level(spec) => 0,
+                _ => usize::MAX,
+            }
+        }
+    }
+}
+
+pub fn sys_reboot(args: &SyscallArgs) -> usize {
+    let Ok(mode) = RebootMode::try_from(args.arg0) else {
+        return usize::MAX;
+    };
+
+    let pid = ProcessId(proc::get_current_pid() as u16);
+    if !proc::init::is_init(pid) {
+        warn!("Process #{} is not allowed to {:?}.", pid, mode);
+        return usize::MAX;
+    }
+
+    if !crate::power::request(mode) {
+        return usize::MAX;
+    }
+
+    // the kernel resets the machine when init exits
+    info!("{:?} requested by #{}", mode, pid);
+    proc::request_terminate();
+    0
+}
+
 pub fn new_sem(key: u32, val: usize) -> usize {
     proc::new_sem(key, val) as usize
 }
@@ -0,0 +1,48 @@
+use crossbeam_queue::ArrayQueue;
+use syscall_def::trace::TraceRecord;
+
+use super::SyscallArgs;
+use crate::proc::ProcessContext;
+
+const TRACE_BUF_SIZE: usize = 512;
+
+lazy_static! {
+    /// Ring buffer of syscall records, the oldest record is dropped when full
+    static ref TRACE_BUF: ArrayQueue<TraceRecord> = ArrayQueue::new(TRACE_BUF_SIZE);
+}
+
+/// Record a syscall made by a traced process at `tick`
+///
+/// `returned` is false if the syscall switched to another process,
+/// in which case `context` no longer holds the caller's rax.
+pub fn record(pid: u16, tick: u64, args: &SyscallArgs, context: &ProcessContext, returned: bool) {
+    let rec = TraceRecord {
+        pid,
+        returned,
+        syscall: args.syscall.clone() as usize,
+        args: [args.arg0, args.arg1, args.arg2],
+        ret: if returned { context.regs.rax } else { 0 },
+        tick,
+    };
+
+    trace!("{} = {:#x} (pid: {})", args, rec.ret, pid);
+
+    TRACE_BUF.force_push(rec);
+}
+
+/// Move the recorded syscalls into `buf`, return the count of records
+pub fn drain(buf: &mut [TraceRecord]) -> usize {
+    let mut count = 0;
+
+    for slot in buf.iter_mut() {
+        match TRACE_BUF.pop() {
+            Some(rec) => {
+                *slot = rec;
+                count += 1;
+            }
+            None => break,
+        }
+    }
+
+    count
+}
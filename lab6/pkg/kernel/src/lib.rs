@@ -25,6 +25,7 @@ pub use utils::*;
 pub mod drivers;
 pub use drivers::*;
 
+pub mod gdb;
 pub mod interrupt;
 pub mod memory;
 pub mod proc;
@@ -32,7 +33,8 @@ pub mod proc;
 pub use alloc::format;
 
 use boot::BootInfo;
-use uefi::{Status, runtime::ResetType};
+use syscall_def::power::RebootMode;
+use syscall_def::signal::Signal;
 
 pub fn init(boot_info: &'static BootInfo) {
     unsafe {
@@ -42,11 +44,18 @@ pub fn init(boot_info: &'static BootInfo) {
 
     serial::init(); // init serial output
     logger::init(); // init logger system
+    params::init(boot_info.cmdline.as_str()); // parse kernel parameters
+    logger::configure(); // set log levels from parameters
+    backtrace::init(boot_info); // init kernel symbols
     memory::address::init(boot_info);
+    fbcon::init(boot_info); // init framebuffer console
     memory::gdt::init(); // init gdt
     memory::allocator::init(); // init kernel heap allocator
     memory::init(boot_info); // init memory manager
+    acpi::init(boot_info); // find the APICs and CPUs in ACPI tables
+    ramdisk::init(boot_info); // init initial ramdisk
     proc::init(boot_info); // init process manager
+    gdb::init(); // init gdb stub on COM2
     interrupt::init(); // init interrupts
 
     x86_64::instructions::interrupts::enable();
@@ -55,17 +64,41 @@ pub fn init(boot_info: &'static BootInfo) {
     info!("YatSenOS initialized.");
 }
 
+/// Stop all processes, then power off or reboot as requested
 pub fn shutdown() -> ! {
-    info!("YatSenOS shutting down.");
-    uefi::runtime::reset(ResetType::SHUTDOWN, Status::SUCCESS, None);
+    let mode = power::requested().unwrap_or(RebootMode::PowerOff);
+    info!("YatSenOS shutting down: {:?}.", mode);
+
+    // ask the remaining processes to exit, and kill them after a second
+    let count = proc::request_terminate();
+    if count > 0 && x86_64::instructions::interrupts::are_enabled() {
+        info!("Terminating {} processes...", count);
+
+        let deadline = interrupt::clock::read_counter() + interrupt::clock::HZ.get();
+        while proc::alive_count() > 0 && interrupt::clock::read_counter() < deadline {
+            interrupt::clock::idle(Some(deadline));
+        }
+    }
+
+    let killed = proc::kill_all(Signal::Kill);
+    if killed > 0 {
+        warn!("Killed {} processes which did not exit in time.", killed);
+    }
+
+    // NOTE: no filesystem is mounted yet, and the FAT16 driver and the
+    // block devices do not cache writes, so there is nothing to flush.
+
+    power::reset(mode);
 }
-pub fn wait(init: proc::ProcessId) {
+
+/// Wait for a process to exit, return its exit code
+pub fn wait(pid: proc::ProcessId) -> isize {
     loop {
-        if proc::still_alive(init) {
+        if proc::still_alive(pid) {
             // Why? Check reflection question 5
-            x86_64::instructions::hlt();
+            interrupt::clock::idle(None);
         } else {
-            break;
+            break proc::exit_code(pid).unwrap_or_default();
         }
     }
 }
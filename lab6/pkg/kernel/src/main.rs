@@ -15,37 +15,65 @@ pub fn kernel_main(boot_info: &'static boot::BootInfo) -> ! {
 
     test();
 
-    wait(spawn_init());
+    proc::init::run();
     ysos::shutdown();
 }
 
-pub fn spawn_init() -> proc::ProcessId {
-    proc::spawn("shell").unwrap()
-}
 pub fn test() {
+    use ysos::drivers::{ata, ramdisk};
+
+    let root = ata::ROOT.get();
+
+    if let Some(root_part) = ramdisk::parse_device(root) {
+        match ramdisk::get() {
+            Some(ramdisk) => probe_root(ramdisk, root, root_part),
+            None => error!("Root device {} needs an initrd", root),
+        }
+        return;
+    }
+
+    let Some((bus, drive, root_part)) = ata::parse_device(root) else {
+        error!("Invalid root device: {}", root);
+        return;
+    };
+
+    if let Some(drive) = ata::AtaDrive::open(bus, drive) {
+        probe_root(drive, root, root_part);
+    } else {
+        error!("Failed to open ATA drive");
+    }
+}
+
+fn probe_root<T>(drive: T, root: &str, root_part: Option<usize>)
+where
+    T: storage::BlockDevice<storage::Block512> + Clone,
+{
     use storage::PartitionTable;
-    if let Some(drive) = ysos::drivers::ata::AtaDrive::open(0, 0) {
-        match storage::mbr::MbrTable::parse(drive) {
-            Ok(mbr_table) => {
-                info!("MBR partition table parsed successfully");
-                match mbr_table.partitions() {
-                    Ok(partitions) => {
-                        info!("Found {} active partitions", partitions.len());
-
-                        for (i, partition) in partitions.iter().enumerate() {
-                            info!("Partition {}: {:?}", i, partition);
-                        }
+
+    match storage::mbr::MbrTable::parse(drive) {
+        Ok(mbr_table) => {
+            info!("MBR partition table parsed successfully");
+            match mbr_table.partitions() {
+                Ok(partitions) => {
+                    info!("Found {} active partitions", partitions.len());
+
+                    for (i, partition) in partitions.iter().enumerate() {
+                        info!("Partition {}: {:?}", i, partition);
                     }
-                    Err(e) => {
-                        error!("Failed to get partitions: {:?}", e);
+
+                    match root_part.map(|n| partitions.get(n - 1)) {
+                        Some(Some(part)) => info!("Root device {}: {:?}", root, part),
+                        Some(None) => warn!("Root device {} not found", root),
+                        None => info!("Root device {} is the whole drive", root),
                     }
                 }
-            }
-            Err(e) => {
-                error!("Failed to parse MBR table: {:?}", e);
+                Err(e) => {
+                    error!("Failed to get partitions: {:?}", e);
+                }
             }
         }
-    } else {
-        error!("Failed to open ATA drive");
+        Err(e) => {
+            error!("Failed to parse MBR table: {:?}", e);
+        }
     }
 }
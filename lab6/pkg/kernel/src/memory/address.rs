@@ -16,3 +16,65 @@ pub fn physical_to_virtual(addr: u64) -> u64 {
         .get()
         .expect("PHYSICAL_OFFSET not initialized")
 }
+
+/// Translate a virtual address with the active page table.
+///
+/// This takes no lock, so it can be used while reporting a fault.
+pub fn translate_active(addr: u64) -> Option<x86_64::PhysAddr> {
+    use x86_64::structures::paging::Translate;
+
+    let addr = x86_64::VirtAddr::try_new(addr).ok()?;
+    let offset = *PHYSICAL_OFFSET.get()?;
+
+    unsafe { active_page_table(offset) }.translate_addr(addr)
+}
+
+/// Map an MMIO region at its address in the physical memory mapping
+///
+/// The bootloader only maps the memory in the memory map, registers of
+/// devices found later, e.g. from ACPI tables, are mapped here uncached.
+pub fn map_mmio(addr: u64, size: u64) {
+    use x86_64::structures::paging::{
+        Mapper, Page, PageTableFlags, PhysFrame, Size4KiB, Translate,
+    };
+    use x86_64::{PhysAddr, VirtAddr};
+
+    let offset = *PHYSICAL_OFFSET
+        .get()
+        .expect("PHYSICAL_OFFSET not initialized");
+    let mut mapper = unsafe { active_page_table(offset) };
+    let mut frame_alloc = super::get_frame_alloc_for_sure();
+
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH
+        | PageTableFlags::NO_EXECUTE;
+
+    let start = PhysFrame::<Size4KiB>::containing_address(PhysAddr::new(addr));
+    let end = PhysFrame::containing_address(PhysAddr::new(addr + size.max(1) - 1));
+
+    for frame in PhysFrame::range_inclusive(start, end) {
+        let page = Page::containing_address(VirtAddr::new(physical_to_virtual(
+            frame.start_address().as_u64(),
+        )));
+
+        // already mapped with the physical memory
+        if mapper.translate_addr(page.start_address()).is_some() {
+            continue;
+        }
+
+        unsafe { mapper.map_to(page, frame, flags, &mut *frame_alloc) }
+            .expect("Failed to map MMIO region")
+            .flush();
+    }
+}
+
+/// The page table in CR3, the kernel one before any process runs
+unsafe fn active_page_table(offset: u64) -> x86_64::structures::paging::OffsetPageTable<'static> {
+    use x86_64::structures::paging::{OffsetPageTable, PageTable};
+
+    let (frame, _) = x86_64::registers::control::Cr3::read();
+    let table = (frame.start_address().as_u64() + offset) as *mut PageTable;
+    unsafe { OffsetPageTable::new(&mut *table, x86_64::VirtAddr::new(offset)) }
+}
@@ -0,0 +1,54 @@
+//! Address space layout randomisation for user processes
+//!
+//! Offsets are drawn from RDRAND, or from the TSC if RDRAND is missing.
+//! Only the user stacks and the base of position-independent executables
+//! vary per process; the user heap is shared by all processes, so its base
+//! is randomised once per boot.
+
+use x86_64::instructions::random::RdRand;
+
+use super::PAGE_SIZE;
+use crate::utils::params::Param;
+
+/// Randomise the layout of user address spaces, `aslr=0` for reproducible debugging
+pub static ASLR: Param<bool> = Param::new("aslr", true);
+
+pub fn init() {
+    if !enabled() {
+        info!("ASLR disabled.");
+    } else if RdRand::new().is_none() {
+        warn!("RDRAND not supported, ASLR falls back to TSC.");
+    } else {
+        info!("ASLR enabled.");
+    }
+}
+
+#[inline]
+pub fn enabled() -> bool {
+    ASLR.get()
+}
+
+/// Get a random number, not suitable for cryptography without RDRAND
+pub fn random() -> u64 {
+    if let Some(rand) = RdRand::new().and_then(|r| r.get_u64()) {
+        return rand;
+    }
+
+    // xorshift the TSC, so consecutive calls do not look alike
+    let mut x = unsafe { core::arch::x86_64::_rdtsc() };
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    x
+}
+
+/// Get a random page aligned offset below `pages` pages
+///
+/// Always 0 if ASLR is disabled.
+pub fn offset(pages: u64) -> u64 {
+    if !enabled() || pages == 0 {
+        return 0;
+    }
+
+    random() % pages * PAGE_SIZE
+}
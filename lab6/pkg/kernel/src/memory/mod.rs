@@ -1,5 +1,6 @@
 pub mod address;
 pub mod allocator;
+pub mod aslr;
 mod frames;
 pub mod user;
 
@@ -38,6 +39,8 @@ pub fn init(boot_info: &'static boot::BootInfo) {
 
     info!("Frame Allocator initialized.");
 
+    aslr::init();
+
     user::init();
     info!("User Heap Allocator initialized.");
 }
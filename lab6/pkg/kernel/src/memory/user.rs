@@ -1,4 +1,5 @@
 use crate::proc::PageTableContext;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use linked_list_allocator::LockedHeap;
 use x86_64::VirtAddr;
 use x86_64::structures::paging::{
@@ -8,13 +9,29 @@ use x86_64::structures::paging::{
 pub const USER_HEAP_START: usize = 0x4000_0000_0000;
 pub const USER_HEAP_SIZE: usize = 1024 * 1024; // 1 MiB
 const USER_HEAP_PAGE: usize = USER_HEAP_SIZE / crate::memory::PAGE_SIZE as usize;
+/// The heap base is randomised within this count of pages
+const USER_HEAP_ASLR_PAGES: u64 = 0x10000;
+/// End of the range the heap may be placed in
+pub const USER_HEAP_END: usize = USER_HEAP_START
+    + USER_HEAP_ASLR_PAGES as usize * crate::memory::PAGE_SIZE as usize
+    + USER_HEAP_SIZE;
 
 pub static USER_ALLOCATOR: LockedHeap = LockedHeap::empty();
 
+static USER_HEAP_BASE: AtomicUsize = AtomicUsize::new(USER_HEAP_START);
+
+/// The base of user heap, shared by all processes
+///
+/// It is randomised once at boot, unlike the stacks and PIE bases which
+/// are randomised for every process.
+pub fn heap_base() -> usize {
+    USER_HEAP_BASE.load(Ordering::Relaxed)
+}
+
 // NOTE: export mod user / call in the kernel init / after frame allocator
 pub fn init() {
     init_user_heap().expect("User Heap Initialization Failed.");
-    info!("User Heap Initialized.");
+    info!("User Heap Initialized at {:#x}.", heap_base());
 }
 
 pub fn init_user_heap() -> Result<(), MapToError<Size4KiB>> {
@@ -23,8 +40,11 @@ pub fn init_user_heap() -> Result<(), MapToError<Size4KiB>> {
     // Get global frame allocator
     let frame_allocator = &mut *super::get_frame_alloc_for_sure();
 
+    let heap_base = USER_HEAP_START + super::aslr::offset(USER_HEAP_ASLR_PAGES) as usize;
+    USER_HEAP_BASE.store(heap_base, Ordering::Relaxed);
+
     elf::map_range(
-        USER_HEAP_START as u64,
+        heap_base as u64,
         USER_HEAP_PAGE as u64,
         mapper,
         frame_allocator,
@@ -35,7 +55,7 @@ pub fn init_user_heap() -> Result<(), MapToError<Size4KiB>> {
     unsafe {
         USER_ALLOCATOR
             .lock()
-            .init(USER_HEAP_START as *mut u8, USER_HEAP_SIZE);
+            .init(heap_base as *mut u8, USER_HEAP_SIZE);
     }
 
     Ok(())
@@ -1,6 +1,6 @@
 use volatile::{VolatileRef, access::ReadOnly};
 use x86_64::{
-    VirtAddr,
+    PrivilegeLevel, VirtAddr,
     registers::rflags::RFlags,
     structures::{gdt::SegmentSelector, idt::InterruptStackFrameValue},
 };
@@ -34,6 +34,12 @@ impl ProcessContext {
         VolatileRef::from_ref(&self.value)
     }
 
+    /// Check if the context was saved from user mode
+    #[inline]
+    pub fn is_user(&self) -> bool {
+        self.value.stack_frame.code_segment.rpl() == PrivilegeLevel::Ring3
+    }
+
     #[inline]
     pub fn set_rax(&mut self, value: usize) {
         self.value.regs.rax = value;
@@ -0,0 +1,320 @@
+//! ELF core dumps of crashed user processes
+//!
+//! NOTE: the FAT16 driver cannot write files yet, so no file is written
+//! to `/CORE`. The core is printed to the serial console instead, as hex
+//! lines between `CORE BEGIN <path>` and `CORE END`. `ysos.py run` logs the console to `serial.log`, and
+//! `ysos.py cores` turns the dumps in it back into files on the host.
+//!
+//! The dump is printed from the fault handler with interrupts disabled,
+//! so it is opt-in: the `Core` limit of processes is `core_limit` from the
+//! command line, 0 by default.
+
+use alloc::vec::Vec;
+use syscall_def::limit::Rlimit;
+use syscall_def::signal::Signal;
+use x86_64::structures::paging::PageTableFlags;
+
+use super::{ProcessContext, ProcessId, process::ProcessInner};
+use crate::memory::PAGE_SIZE;
+use crate::utils::params::Param;
+
+/// Default size limit of core dumps in bytes, 0 disables them
+pub static CORE_LIMIT: Param<usize> = Param::new("core_limit", 0);
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+
+/// Size of `struct elf_prstatus` on x86_64
+const PRSTATUS_SIZE: usize = 336;
+/// Offset of `pr_reg` in `struct elf_prstatus`
+const PRSTATUS_REG_OFFSET: usize = 112;
+/// Size of `struct elf_prpsinfo` on x86_64
+const PRPSINFO_SIZE: usize = 136;
+
+/// Bytes of core data per line on the console
+const LINE_SIZE: usize = 32;
+
+/// A run of contiguous pages with the same flags
+struct Segment {
+    start: u64,
+    pages: u64,
+    flags: PageTableFlags,
+}
+
+/// Write a core dump of the current process if its core limit allows
+///
+/// the page table of the process must be active
+pub fn dump(proc: &ProcessInner, pid: ProcessId, signal: Signal, context: &ProcessContext) {
+    let limit = proc.rlimit(Rlimit::Core);
+    if limit == 0 {
+        return;
+    }
+
+    // only the headers are built in memory, the pages are printed in place
+    let segments = segments(proc);
+    let notes = notes(proc, pid, signal, context);
+    let data_offset = data_offset(segments.len(), notes.len());
+    let size = segments
+        .iter()
+        .fold(data_offset as u64, |size, s| size + s.pages * PAGE_SIZE);
+
+    if size > limit as u64 {
+        warn!(
+            "Core dump of {}#{} exceeds the limit: {} > {} bytes",
+            proc.name(),
+            pid,
+            size,
+            limit
+        );
+        return;
+    }
+
+    info!(
+        "Dumping core of {}#{} to /CORE/{}.{} ({} bytes)",
+        proc.name(),
+        pid,
+        proc.name(),
+        pid,
+        size
+    );
+
+    println!("CORE BEGIN /CORE/{}.{}", proc.name(), pid);
+    print_hex(&headers(&segments, &notes, data_offset));
+    for segment in segments.iter() {
+        let data = unsafe {
+            core::slice::from_raw_parts(
+                segment.start as *const u8,
+                (segment.pages * PAGE_SIZE) as usize,
+            )
+        };
+        print_hex(data);
+    }
+    println!("CORE END");
+}
+
+/// Print `data` as hex lines, its length is a multiple of `LINE_SIZE`
+fn print_hex(data: &[u8]) {
+    for line in data.chunks(LINE_SIZE) {
+        for byte in line {
+            print!("{:02x}", byte);
+        }
+        println!();
+    }
+}
+
+/// Offset of the page contents, after the headers and notes
+fn data_offset(segments: usize, notes: usize) -> usize {
+    let phnum = segments + 1;
+    (EHDR_SIZE + phnum * PHDR_SIZE + notes).next_multiple_of(PAGE_SIZE as usize)
+}
+
+/// Group the mapped pages of the process into segments
+fn segments(proc: &ProcessInner) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+
+    for (page, flags) in proc.vm().mapped_pages() {
+        let start = page.start_address().as_u64();
+        match segments.last_mut() {
+            Some(last) if last.start + last.pages * PAGE_SIZE == start && last.flags == flags => {
+                last.pages += 1;
+            }
+            _ => segments.push(Segment {
+                start,
+                pages: 1,
+                flags,
+            }),
+        }
+    }
+
+    segments
+}
+
+/// Build the ELF header, program headers and notes, padded to `data_offset`
+fn headers(segments: &[Segment], notes: &[u8], data_offset: usize) -> Vec<u8> {
+    let phnum = segments.len() + 1;
+    let notes_offset = EHDR_SIZE + phnum * PHDR_SIZE;
+
+    let mut core = Vec::with_capacity(data_offset);
+
+    // ELF header
+    core.extend_from_slice(b"\x7fELF");
+    core.extend_from_slice(&[2, 1, 1, 0]); // 64-bit, little endian, version 1, SysV
+    core.extend_from_slice(&[0; 8]);
+    push_u16(&mut core, 4); // ET_CORE
+    push_u16(&mut core, 62); // EM_X86_64
+    push_u32(&mut core, 1);
+    push_u64(&mut core, 0); // e_entry
+    push_u64(&mut core, EHDR_SIZE as u64); // e_phoff
+    push_u64(&mut core, 0); // e_shoff
+    push_u32(&mut core, 0); // e_flags
+    push_u16(&mut core, EHDR_SIZE as u16);
+    push_u16(&mut core, PHDR_SIZE as u16);
+    push_u16(&mut core, phnum as u16);
+    push_u16(&mut core, 0); // e_shentsize
+    push_u16(&mut core, 0); // e_shnum
+    push_u16(&mut core, 0); // e_shstrndx
+
+    // program headers
+    push_phdr(
+        &mut core,
+        PT_NOTE,
+        0,
+        notes_offset as u64,
+        0,
+        notes.len() as u64,
+        4,
+    );
+
+    let mut offset = data_offset as u64;
+    for segment in segments {
+        let size = segment.pages * PAGE_SIZE;
+        push_phdr(
+            &mut core,
+            PT_LOAD,
+            segment_flags(segment.flags),
+            offset,
+            segment.start,
+            size,
+            PAGE_SIZE,
+        );
+        offset += size;
+    }
+
+    core.extend_from_slice(notes);
+    core.resize(data_offset, 0);
+    core
+}
+
+/// Build the `NT_PRSTATUS` and `NT_PRPSINFO` notes
+fn notes(proc: &ProcessInner, pid: ProcessId, signal: Signal, context: &ProcessContext) -> Vec<u8> {
+    let ppid = proc.parent().map_or(0, |p| p.pid().0 as u32);
+    let regs = &context.regs;
+    let frame = &context.stack_frame;
+
+    let mut status = Vec::with_capacity(PRSTATUS_SIZE);
+    push_u32(&mut status, signal as u32); // si_signo
+    push_u32(&mut status, 0); // si_code
+    push_u32(&mut status, 0); // si_errno
+    push_u16(&mut status, signal as u16); // pr_cursig
+    status.resize(32, 0);
+    push_u32(&mut status, pid.0 as u32);
+    push_u32(&mut status, ppid);
+    status.resize(PRSTATUS_REG_OFFSET, 0);
+
+    // struct user_regs_struct
+    for value in [
+        regs.r15 as u64,
+        regs.r14 as u64,
+        regs.r13 as u64,
+        regs.r12 as u64,
+        regs.rbp as u64,
+        regs.rbx as u64,
+        regs.r11 as u64,
+        regs.r10 as u64,
+        regs.r9 as u64,
+        regs.r8 as u64,
+        regs.rax as u64,
+        regs.rcx as u64,
+        regs.rdx as u64,
+        regs.rsi as u64,
+        regs.rdi as u64,
+        u64::MAX, // orig_rax
+        frame.instruction_pointer.as_u64(),
+        frame.code_segment.0 as u64,
+        frame.cpu_flags.bits(),
+        frame.stack_pointer.as_u64(),
+        frame.stack_segment.0 as u64,
+        proc.fs_base().as_u64(),
+        0,                            // gs_base
+        frame.stack_segment.0 as u64, // ds
+        frame.stack_segment.0 as u64, // es
+        0,                            // fs
+        0,                            // gs
+    ] {
+        push_u64(&mut status, value);
+    }
+    status.resize(PRSTATUS_SIZE, 0);
+
+    let mut psinfo = Vec::with_capacity(PRPSINFO_SIZE);
+    psinfo.extend_from_slice(&[0, b'R', 0, 0]); // state, sname, zomb, nice
+    psinfo.resize(24, 0);
+    push_u32(&mut psinfo, pid.0 as u32);
+    push_u32(&mut psinfo, ppid);
+    psinfo.resize(40, 0);
+
+    let name = proc.name().as_bytes();
+    let len = name.len().min(15);
+    psinfo.extend_from_slice(&name[..len]);
+    psinfo.resize(56, 0); // pr_fname
+    psinfo.extend_from_slice(&name[..name.len().min(79)]);
+    psinfo.resize(PRPSINFO_SIZE, 0); // pr_psargs
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRSTATUS, &status);
+    push_note(&mut notes, NT_PRPSINFO, &psinfo);
+    notes
+}
+
+fn segment_flags(flags: PageTableFlags) -> u32 {
+    let mut p_flags = PF_R;
+    if flags.contains(PageTableFlags::WRITABLE) {
+        p_flags |= PF_W;
+    }
+    if !flags.contains(PageTableFlags::NO_EXECUTE) {
+        p_flags |= PF_X;
+    }
+    p_flags
+}
+
+fn push_phdr(
+    buf: &mut Vec<u8>,
+    p_type: u32,
+    p_flags: u32,
+    offset: u64,
+    vaddr: u64,
+    size: u64,
+    align: u64,
+) {
+    push_u32(buf, p_type);
+    push_u32(buf, p_flags);
+    push_u64(buf, offset);
+    push_u64(buf, vaddr);
+    push_u64(buf, 0); // p_paddr
+    push_u64(buf, size); // p_filesz
+    push_u64(buf, size); // p_memsz
+    push_u64(buf, align);
+}
+
+fn push_note(buf: &mut Vec<u8>, n_type: u32, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+
+    push_u32(buf, NAME.len() as u32);
+    push_u32(buf, desc.len() as u32);
+    push_u32(buf, n_type);
+    buf.extend_from_slice(NAME);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+    buf.extend_from_slice(desc);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+fn push_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
@@ -1,27 +1,59 @@
 use super::*;
 use crate::proc::sync::SemaphoreSet;
-use crate::utils::resource::ResourceSet;
+use crate::proc::vm::stack::STACK_MAX_SIZE;
+use crate::utils::resource::{Resource, ResourceSet, StdIO};
 use alloc::{collections::BTreeMap, sync::Arc};
 use spin::RwLock;
+use syscall_def::limit::{RLIM_INFINITY, RLIMIT_COUNT, Rlimit};
 use x86_64::structures::paging::{
     Page,
     page::{PageRange, PageRangeInclusive},
 };
+
+/// Limit of a process without parent
+fn default_rlimit(res: Rlimit) -> usize {
+    match res {
+        Rlimit::Stack => STACK_MAX_SIZE as usize,
+        Rlimit::Heap => RLIM_INFINITY,
+        Rlimit::Files => u8::MAX as usize + 1,
+        Rlimit::Children => 64,
+        Rlimit::Cpu => RLIM_INFINITY,
+        Rlimit::Core => coredump::CORE_LIMIT.get(),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ProcessData {
     // shared data
     pub(super) env: Arc<RwLock<BTreeMap<String, String>>>,
     pub(super) resource: Arc<RwLock<ResourceSet>>,
     pub(super) semaphore: Arc<RwLock<SemaphoreSet>>,
+
+    // private data
+    pub(super) cwd: String,
+    /// arguments passed by the spawner, separated by spaces
+    pub(super) args: String,
+    pub(super) rlimit: [usize; RLIMIT_COUNT],
+    pub(super) heap_usage: usize,
 }
 
 impl Default for ProcessData {
     fn default() -> Self {
-        Self {
+        let data = Self {
             env: Arc::new(RwLock::new(BTreeMap::new())),
             resource: Arc::new(RwLock::new(ResourceSet::default())),
             semaphore: Arc::new(RwLock::new(SemaphoreSet::default())),
+            cwd: String::from("/"),
+            args: String::new(),
+            rlimit: core::array::from_fn(|i| default_rlimit(Rlimit::try_from(i).unwrap())),
+            heap_usage: 0,
+        };
+
+        for stdio in [StdIO::Stdin, StdIO::Stdout, StdIO::Stderr] {
+            data.open(Resource::Console(stdio));
         }
+
+        data
     }
 }
 
@@ -37,6 +69,59 @@ impl ProcessData {
     pub fn set_env(&mut self, key: &str, val: &str) {
         self.env.write().insert(key.into(), val.into());
     }
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    /// Change the working directory, `path` must be resolved
+    pub fn set_cwd(&mut self, path: &str) {
+        self.cwd = path.into();
+    }
+
+    pub fn args(&self) -> &str {
+        &self.args
+    }
+
+    pub fn set_args(&mut self, args: &str) {
+        self.args = args.into();
+    }
+
+    pub fn rlimit(&self, res: Rlimit) -> usize {
+        self.rlimit[res as usize]
+    }
+
+    /// Set the limit of a resource, limits can only be lowered
+    pub fn set_rlimit(&mut self, res: Rlimit, val: usize) -> bool {
+        let limit = &mut self.rlimit[res as usize];
+        if val > *limit {
+            return false;
+        }
+
+        *limit = val;
+        true
+    }
+
+    /// Account `size` bytes of heap, fail if the heap limit is exceeded
+    pub fn charge_heap(&mut self, size: usize) -> bool {
+        let usage = self.heap_usage.saturating_add(size);
+        if usage > self.rlimit(Rlimit::Heap) {
+            return false;
+        }
+
+        self.heap_usage = usage;
+        true
+    }
+
+    pub fn uncharge_heap(&mut self, size: usize) {
+        // memory allocated before fork may be freed by the child
+        self.heap_usage = self.heap_usage.saturating_sub(size);
+    }
+
+    /// Open a resource, fail if the fd limit is reached
+    pub fn open(&self, res: Resource) -> Option<u8> {
+        self.resource.write().open(res, self.rlimit(Rlimit::Files))
+    }
+
     pub fn write(&self, fd: u8, buf: &[u8]) -> isize {
         self.resource.read().write(fd, buf)
     }
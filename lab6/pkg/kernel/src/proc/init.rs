@@ -0,0 +1,110 @@
+//! The first user process and its respawn policy
+
+use core::sync::atomic::{AtomicU16, Ordering};
+use syscall_def::signal::Signal;
+
+use super::ProcessId;
+use crate::drivers::power;
+use crate::utils::params::{self, Param, ParamValue};
+
+/// The first user program, its arguments follow `--` on the command line
+pub static INIT: Param<&str> = Param::new("init", "shell");
+
+/// When to start init again after it exits
+pub static INIT_RESPAWN: Param<Respawn> = Param::new("init_respawn", Respawn::Crash);
+
+/// Give up respawning init after this many times
+const MAX_RESPAWNS: usize = 8;
+
+/// Pid of the running init
+static INIT_PID: AtomicU16 = AtomicU16::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Respawn {
+    /// Shut down when init exits
+    Never,
+    /// Respawn init if it was killed by a signal
+    Crash,
+    /// Respawn init whenever it exits
+    Always,
+}
+
+impl ParamValue for Respawn {
+    fn parse(value: &'static str) -> Option<Self> {
+        match value {
+            "never" => Some(Self::Never),
+            "crash" => Some(Self::Crash),
+            "always" => Some(Self::Always),
+            _ => None,
+        }
+    }
+}
+
+impl Respawn {
+    fn should_respawn(self, signal: Option<Signal>) -> bool {
+        match self {
+            Self::Never => false,
+            Self::Crash => signal.is_some(),
+            Self::Always => true,
+        }
+    }
+}
+
+/// Spawn init with its arguments
+pub fn spawn() -> Option<ProcessId> {
+    let init = INIT.get();
+    let args = params::init_args();
+
+    let pid = super::spawn(&alloc::format!("{} {}", init, args));
+    match pid {
+        Some(pid) => {
+            INIT_PID.store(pid.0, Ordering::Relaxed);
+            info!("Init {}#{} started: {}", init, pid, args)
+        }
+        None => error!("Failed to spawn init: {}", init),
+    }
+    pid
+}
+
+/// Whether the process is init, which may power off or reboot
+pub fn is_init(pid: ProcessId) -> bool {
+    pid.0 == INIT_PID.load(Ordering::Relaxed)
+}
+
+/// Whether the process is init or was spawned by it, directly or not
+pub fn is_init_or_descendant(pid: ProcessId) -> bool {
+    let init = ProcessId(INIT_PID.load(Ordering::Relaxed));
+    init.0 != 0
+        && x86_64::instructions::interrupts::without_interrupts(|| {
+            super::manager::get_process_manager().is_descendant(pid, init)
+        })
+}
+
+/// Run init until it exits for good, by the respawn policy
+pub fn run() {
+    let policy = INIT_RESPAWN.get();
+
+    for respawns in 0.. {
+        let Some(pid) = spawn() else {
+            return;
+        };
+
+        let ret = crate::wait(pid);
+        if let Some(mode) = power::requested() {
+            info!("Init #{} exited with {} for {:?}", pid, ret, mode);
+            return;
+        }
+
+        if !policy.should_respawn(super::killed_by(pid)) {
+            info!("Init #{} exited with {}", pid, ret);
+            return;
+        }
+
+        if respawns == MAX_RESPAWNS {
+            error!("Init exited {} times, giving up.", respawns + 1);
+            return;
+        }
+
+        warn!("Init #{} exited with {}, respawning...", pid, ret);
+    }
+}
@@ -1,5 +1,6 @@
 use super::*;
 use crate::{
+    interrupt::clock,
     memory::{
         self, PAGE_SIZE,
         allocator::{ALLOCATOR, HEAP_SIZE},
@@ -11,10 +12,16 @@ use alloc::{
     collections::*,
     format,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use spin::{Mutex, RwLock};
+use syscall_def::info::ProcessTimes;
+use syscall_def::limit::Rlimit;
 use x86::current;
 
+/// Maximum count of alive processes in the system
+pub const MAX_PROCESS_COUNT: usize = 128;
+
 pub static PROCESS_MANAGER: spin::Once<ProcessManager> = spin::Once::new();
 
 pub fn init(init: Arc<Process>, app_list: boot::AppListRef) {
@@ -74,14 +81,41 @@ impl ProcessManager {
             .expect("No current process")
     }
 
+    /// Pid of the running process, without taking any lock
+    #[inline]
+    pub fn current_pid(&self) -> ProcessId {
+        processor::get_pid()
+    }
+
+    /// Visit alive processes without blocking on their locks
+    ///
+    /// Return false if the process list is locked, processes which are
+    /// locked are skipped. This is safe to call from a debugger stop.
+    pub fn try_for_each(&self, mut f: impl FnMut(&Process, &ProcessInner)) -> bool {
+        let Some(processes) = self.processes.try_read() else {
+            return false;
+        };
+
+        for proc in processes.values() {
+            if let Some(inner) = proc.try_read()
+                && inner.status() != ProgramStatus::Dead
+            {
+                f(proc, &inner);
+            }
+        }
+
+        true
+    }
+
     pub fn save_current(&self, context: &ProcessContext) {
         let proc = self.current();
-        let mut proc_write = proc.write();
-        proc_write.tick();
-        proc_write.save(context);
+        proc.write().save(context);
     }
 
     pub fn switch_next(&self, context: &mut ProcessContext) -> ProcessId {
+        // charge the kernel time of the outgoing process before restoring
+        self.current().write().leave_kernel(clock::read_tsc());
+
         let next_pid = loop {
             if let Some(pid) = self.ready_queue.lock().pop_front() {
                 if let Some(proc) = self.get_proc(&pid) {
@@ -143,7 +177,15 @@ impl ProcessManager {
     pub fn handle_page_fault(&self, addr: VirtAddr, err_code: PageFaultErrorCode) -> bool {
         if !err_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
             let current = self.current();
-            let mut inner = current.write();
+            // the kernel faulted while holding the lock, waiting would deadlock
+            let Some(mut inner) = current.try_write() else {
+                warn!(
+                    "Page fault at {:#x} while process #{} is locked",
+                    addr.as_u64(),
+                    current.pid()
+                );
+                return false;
+            };
 
             trace!(
                 "Handling page fault at {:#x} for process {}",
@@ -182,6 +224,12 @@ impl ProcessManager {
 
         proc.kill(ret);
 
+        // processes are never reaped, children times are accumulated on exit
+        if let Some(parent) = proc.read().parent() {
+            let times = proc.read().times(clock::read_tsc());
+            parent.write().add_child_times(&times);
+        }
+
         if let Some(pids) = self.wait_queue.lock().remove(&pid) {
             for waiter_pid in pids {
                 self.wake_up(waiter_pid, Some(ret));
@@ -193,6 +241,58 @@ impl ProcessManager {
         }
     }
 
+    /// Pids of the alive user processes
+    fn user_pids(&self) -> Vec<ProcessId> {
+        self.processes
+            .read()
+            .values()
+            .filter(|p| p.pid() != KERNEL_PID && p.read().status() != ProgramStatus::Dead)
+            .map(|p| p.pid())
+            .collect()
+    }
+
+    /// Ask all user processes to terminate, return the count of them
+    pub fn request_terminate(&self) -> usize {
+        let pids = self.user_pids();
+        for pid in pids.iter() {
+            if let Some(proc) = self.get_proc(pid) {
+                proc.write().request_terminate();
+            }
+        }
+        pids.len()
+    }
+
+    /// Kill the process by `signal`, which is recorded for `killed_by`
+    pub fn kill_by_signal(&self, pid: ProcessId, signal: Signal) {
+        if let Some(proc) = self.get_proc(&pid)
+            && proc.read().status() != ProgramStatus::Dead
+        {
+            proc.write().set_signal(signal);
+        }
+        self.kill(pid, signal.exit_code());
+    }
+
+    /// Kill all user processes by `signal`, return the count of them
+    pub fn kill_all(&self, signal: Signal) -> usize {
+        let pids = self.user_pids();
+        for pid in pids.iter() {
+            self.kill_by_signal(*pid, signal);
+        }
+        pids.len()
+    }
+
+    pub fn alive_count(&self) -> usize {
+        self.user_pids().len()
+    }
+
+    /// If any process is waiting in the ready queue to run
+    pub fn has_ready(&self) -> bool {
+        self.ready_queue.lock().iter().any(|pid| {
+            self.get_proc(pid)
+                .is_some_and(|p| p.read().status() == ProgramStatus::Ready)
+        })
+    }
+
     pub fn print_process_list(&self) {
         let mut output = String::from("  PID | PPID | Process Name |  Ticks  | Status\n");
 
@@ -210,6 +310,65 @@ impl ProcessManager {
 
         print!("{}", output);
     }
+    /// Fill `buf` with the records of alive processes
+    ///
+    /// Return the count of alive processes, which may exceed `buf.len()`
+    pub fn process_info(&self, buf: &mut [ProcessInfo]) -> usize {
+        let processes = self.processes.read();
+        let alive = processes
+            .values()
+            .filter(|p| p.read().status() != ProgramStatus::Dead);
+
+        let mut count = 0;
+        for proc in alive {
+            if let Some(slot) = buf.get_mut(count) {
+                *slot = proc.info();
+            }
+            count += 1;
+        }
+
+        count
+    }
+    /// Check if `parent` may create one more child
+    ///
+    /// Fail if the child limit of `parent` or the system limit is reached
+    fn may_create_child(&self, parent: &Arc<Process>) -> bool {
+        let limit = parent.read().rlimit(Rlimit::Children);
+        let processes = self.processes.read();
+        let alive = processes
+            .values()
+            .filter(|p| p.read().status() != ProgramStatus::Dead);
+
+        let mut total = 0;
+        let mut children = 0;
+        for proc in alive {
+            let ppid = proc.read().parent().map(|p| p.pid());
+            total += 1;
+            children += (ppid == Some(parent.pid())) as usize;
+        }
+
+        if total >= MAX_PROCESS_COUNT || children >= limit {
+            warn!(
+                "Process #{} cannot create child: {} children, {} processes",
+                parent.pid(),
+                children,
+                total
+            );
+            return false;
+        }
+
+        true
+    }
+    pub fn times(&self, pid: ProcessId) -> Option<ProcessTimes> {
+        let proc = self.get_proc(&pid)?;
+        let times = proc.read().times(clock::read_tsc());
+        Some(times)
+    }
+    /// The signal which killed the process, `None` if it is alive or exited
+    pub fn killed_by(&self, pid: ProcessId) -> Option<Signal> {
+        self.get_proc(&pid).and_then(|proc| proc.read().signal())
+    }
+
     pub fn get_exit_code(&self, pid: ProcessId) -> Option<isize> {
         //avoid deadlock
         x86_64::instructions::interrupts::without_interrupts(|| {
@@ -221,23 +380,48 @@ impl ProcessManager {
     }
     pub fn spawn(
         &self,
-        elf: &ElfFile,
+        elf: &ElfFile<'static>,
         name: String,
+        args: &str,
         parent: Option<Weak<Process>>,
         proc_data: Option<ProcessData>,
-    ) -> ProcessId {
+    ) -> Option<ProcessId> {
+        // the trace flag, the working directory and the limits are inherited from the parent
+        let parent_proc = parent.as_ref().and_then(|p| p.upgrade());
+        if parent_proc
+            .as_ref()
+            .is_some_and(|p| !self.may_create_child(p))
+        {
+            return None;
+        }
+
         let kproc = self.get_proc(&KERNEL_PID).unwrap();
         let page_table = kproc.read().clone_page_table();
         let page_table_mapper: x86_64::structures::paging::OffsetPageTable<'static> =
             page_table.mapper();
         let proc_vm = Some(ProcessVm::new(page_table));
-        let proc = Process::new(name, parent, proc_vm, proc_data);
+        let traced = parent_proc.as_ref().is_some_and(|p| p.read().is_traced());
+        let mut proc_data = proc_data.unwrap_or_else(|| {
+            let mut data = ProcessData::new();
+            if let Some(p) = parent_proc.as_ref() {
+                let p = p.read();
+                data.set_cwd(p.cwd());
+                data.rlimit = p.rlimit;
+            }
+            data
+        });
+        proc_data.set_args(args);
+        let proc = Process::new(name, parent, proc_vm, Some(proc_data));
 
         let pid = proc.pid();
 
         let mut inner = proc.write();
+        inner.set_traced(traced);
         // 加载 ELF 文件
-        inner.load_elf(elf, page_table_mapper, pid);
+        if let Err(e) = inner.load_elf(elf, page_table_mapper, pid) {
+            warn!("Failed to load ELF for process #{}: {:?}", pid, e);
+            return None;
+        }
         debug!("Load ELF");
         // inner.set_stack_frame(
         //     VirtAddr::new_truncate(elf.header.pt2.entry_point()),
@@ -256,15 +440,50 @@ impl ProcessManager {
         // 将进程添加到就绪队列
         self.push_ready(pid);
 
-        pid
+        Some(pid)
     }
-    pub fn fork(&self) {
+    /// Fork the current process, return `None` if the limits are reached
+    pub fn fork(&self) -> Option<ProcessId> {
         let current = self.current();
-        let child = current.fork();
-        self.push_ready(child.pid());
-        self.add_proc(child.pid(), child);
+        if !self.may_create_child(&current) {
+            return None;
+        }
+
+        let child = match current.fork() {
+            Ok(child) => child,
+            Err(err) => {
+                warn!("Failed to fork process #{}: {:?}", current.pid(), err);
+                return None;
+            }
+        };
+        let pid = child.pid();
+        self.push_ready(pid);
+        self.add_proc(pid, child);
 
         debug!("Ready queue: {:?}", self.ready_queue.lock());
+
+        Some(pid)
+    }
+    /// If `pid` is `ancestor` or one of its descendants
+    pub fn is_descendant(&self, pid: ProcessId, ancestor: ProcessId) -> bool {
+        let mut proc = self.get_proc(&pid);
+        while let Some(p) = proc {
+            if p.pid() == ancestor {
+                return true;
+            }
+            proc = p.read().parent();
+        }
+        false
+    }
+
+    pub fn set_traced(&self, pid: ProcessId, traced: bool) -> bool {
+        if let Some(proc) = self.get_proc(&pid) {
+            proc.write().set_traced(traced);
+            trace!("Process #{} traced: {}", pid, traced);
+            true
+        } else {
+            false
+        }
     }
     pub fn block(&self, pid: ProcessId) {
         if let Some(proc) = self.get_proc(&pid) {
@@ -273,12 +492,13 @@ impl ProcessManager {
             trace!("Process #{} blocked", pid);
         }
     }
-    pub fn wait_pid(&self, pid: ProcessId) {
+    /// Add current process to the waiters of `pid`, fail if it does not exist
+    pub fn wait_pid(&self, pid: ProcessId) -> bool {
         let current_pid = processor::get_pid();
 
         if self.get_proc(&pid).is_none() {
             debug!("Process #{} not found, cannot wait", pid);
-            return;
+            return false;
         }
 
         let mut wait_queue = self.wait_queue.lock();
@@ -288,6 +508,8 @@ impl ProcessManager {
             .insert(current_pid);
 
         trace!("Process #{} is waiting for process #{}", current_pid, pid);
+
+        true
     }
     /// Wake up the process with the given pid
     ///
@@ -1,5 +1,7 @@
 pub mod context;
+pub mod coredump;
 mod data;
+pub mod init;
 pub mod manager;
 mod paging;
 mod pid;
@@ -10,7 +12,6 @@ mod vm;
 
 use crate::memory::PAGE_SIZE;
 use alloc::sync::Arc;
-use alloc::vec::Vec;
 use manager::*;
 use process::*;
 
@@ -25,7 +26,11 @@ use x86_64::VirtAddr;
 use x86_64::structures::idt::PageFaultErrorCode;
 pub const KERNEL_PID: ProcessId = ProcessId(1);
 
+use crate::interrupt::clock;
 use sync::SemaphoreResult;
+use syscall_def::info::{AppInfo, ProcessInfo, ProcessStatus, ProcessTimes, name_buf};
+use syscall_def::limit::Rlimit;
+use syscall_def::signal::Signal;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ProgramStatus {
@@ -35,6 +40,17 @@ pub enum ProgramStatus {
     Dead,
 }
 
+impl From<ProgramStatus> for ProcessStatus {
+    fn from(status: ProgramStatus) -> Self {
+        match status {
+            ProgramStatus::Running => ProcessStatus::Running,
+            ProgramStatus::Ready => ProcessStatus::Ready,
+            ProgramStatus::Blocked => ProcessStatus::Blocked,
+            ProgramStatus::Dead => ProcessStatus::Dead,
+        }
+    }
+}
+
 /// init process manager
 pub fn init(boot_info: &'static boot::BootInfo) {
     let proc_vm = ProcessVm::new(PageTableContext::new()).init_kernel_vm();
@@ -59,12 +75,23 @@ pub fn init(boot_info: &'static boot::BootInfo) {
 pub fn switch(context: &mut ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let process_manager = get_process_manager();
-        process_manager.save_current(context);
         let current = process_manager.current();
         let pid = current.pid();
 
+        let mut inner = current.write();
+        inner.enter_kernel(clock::read_tsc());
+        inner.tick();
+        drop(inner);
+
+        process_manager.save_current(context);
+
         if current.read().status() == ProgramStatus::Ready {
-            process_manager.push_ready(pid);
+            if current.read().cpu_exhausted() {
+                warn!("Process #{} exceeded its CPU limit", pid);
+                process_manager.kill_by_signal(pid, Signal::XCpu);
+            } else {
+                process_manager.push_ready(pid);
+            }
         }
 
         process_manager.switch_next(context);
@@ -78,25 +105,41 @@ pub fn switch(context: &mut ProcessContext) {
 //         get_process_manager().spawn_kernel_thread(entry, name, data)
 //     })
 // }
-pub fn spawn(name: &str) -> Option<ProcessId> {
+/// Spawn an app by its name or by its path, followed by its arguments
+///
+/// Paths are resolved against the working directory, apps live in `/APP`.
+pub fn spawn(command: &str) -> Option<ProcessId> {
+    let command = command.trim();
+    let (path, args) = command.split_once(' ').unwrap_or((command, ""));
+    let name = if path.contains(storage::PATH_SEPARATOR) {
+        let path = resolve_path(path);
+        let (dir, name) = path.rsplit_once(storage::PATH_SEPARATOR)?;
+        if !dir.eq_ignore_ascii_case("/app") {
+            return None;
+        }
+        name.to_string()
+    } else {
+        path.to_string()
+    };
+
     let app = x86_64::instructions::interrupts::without_interrupts(|| {
         let app_list = get_process_manager().app_list()?;
-        app_list.iter().find(|&app| app.name.eq(name))
+        app_list.iter().find(|&app| app.name.eq(name.as_str()))
     })?;
 
-    elf_spawn(name.to_string(), &app.elf)
+    elf_spawn(name, args.trim_start(), &app.elf)
 }
 use xmas_elf::ElfFile;
-pub fn elf_spawn(name: String, elf: &ElfFile) -> Option<ProcessId> {
+pub fn elf_spawn(name: String, args: &str, elf: &ElfFile<'static>) -> Option<ProcessId> {
     let pid = x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
         let process_name = name.to_lowercase();
         let parent = Arc::downgrade(&manager.current());
-        let pid = manager.spawn(elf, name, Some(parent), None);
+        let pid = manager.spawn(elf, name, args, Some(parent), None)?;
 
         debug!("Spawned process: {}#{}", process_name, pid);
-        pid
-    });
+        Some(pid)
+    })?;
 
     Some(pid)
 }
@@ -112,6 +155,38 @@ pub fn env(key: &str) -> Option<String> {
     })
 }
 
+pub fn cwd() -> String {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().read().cwd().to_string()
+    })
+}
+
+/// Resolve `path` against the working directory of current process
+pub fn resolve_path(path: &str) -> String {
+    storage::resolve_path(&cwd(), path)
+}
+
+/// Directories which exist without a filesystem, apps are spawned from `/APP`
+const BUILTIN_DIRS: &[&str] = &["/", "/app"];
+
+/// Change the working directory of current process, fails if it does not exist
+pub fn chdir(path: &str) -> bool {
+    let path = resolve_path(path);
+
+    // NOTE: no filesystem is mounted yet, so only these directories exist
+    let exists = BUILTIN_DIRS
+        .iter()
+        .any(|dir| dir.eq_ignore_ascii_case(&path));
+    if !exists {
+        return false;
+    }
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().write().set_cwd(&path);
+    });
+    true
+}
+
 pub fn process_exit(ret: isize) -> ! {
     x86_64::instructions::interrupts::without_interrupts(|| {
         get_process_manager().kill_current(ret);
@@ -127,6 +202,15 @@ pub fn handle_page_fault(addr: VirtAddr, err_code: PageFaultErrorCode) -> bool {
         get_process_manager().handle_page_fault(addr, err_code)
     })
 }
+/// Check if the fault at `addr` is an overflow of current process's stack
+pub fn is_stack_overflow(addr: VirtAddr) -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager()
+            .current()
+            .read()
+            .is_stack_overflow(addr)
+    })
+}
 pub fn list_app() {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let app_list = get_process_manager().app_list();
@@ -135,24 +219,65 @@ pub fn list_app() {
             return;
         }
 
-        let apps = app_list
-            .unwrap()
-            .iter()
-            .map(|app| app.name.as_str())
-            .collect::<Vec<&str>>()
-            .join(", ");
+        println!("[+] App list:");
+        for app in app_list.unwrap().iter() {
+            let (size, unit) = crate::humanized_size(app.elf.input.len() as u64);
+            println!(
+                "    {:<16} {:>7.2} {:<3} entry: {:#x}",
+                app.name.as_str(),
+                size,
+                unit,
+                app.elf.header.pt2.entry_point()
+            );
+        }
+    });
+}
+/// Fill `buf` with the records of alive processes, return the count of them
+pub fn list_process_info(buf: &mut [ProcessInfo]) -> usize {
+    fault_in(buf);
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().process_info(buf))
+}
+/// Fill `buf` with the records of loaded apps, return the count of them
+pub fn list_app_info(buf: &mut [AppInfo]) -> usize {
+    fault_in(buf);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let Some(app_list) = get_process_manager().app_list() else {
+            return 0;
+        };
 
-        // TODO: print more information like size, entry point, etc.
+        for (slot, app) in buf.iter_mut().zip(app_list.iter()) {
+            *slot = AppInfo {
+                name: name_buf(app.name.as_str()),
+                size: app.elf.input.len(),
+                entry: app.elf.header.pt2.entry_point() as usize,
+            };
+        }
 
-        println!("[+] App list: {}", apps);
-    });
+        app_list.len()
+    })
+}
+/// Touch every page of a user buffer before a process lock is taken
+///
+/// Segments are loaded on page fault, which takes the lock of the current
+/// process, so the kernel must not fault on them while holding it.
+pub fn fault_in<T>(buf: &[T]) {
+    let ptr = buf.as_ptr() as *const u8;
+    let len = core::mem::size_of_val(buf);
+    let Some(last) = len.checked_sub(1) else {
+        return;
+    };
+    for offset in (0..len).step_by(PAGE_SIZE as usize).chain([last]) {
+        unsafe { core::ptr::read_volatile(ptr.add(offset)) };
+    }
 }
 pub fn read(fd: u8, buf: &mut [u8]) -> isize {
+    fault_in(buf);
     x86_64::instructions::interrupts::without_interrupts(|| {
         get_process_manager().current().read().read(fd, buf)
     })
 }
 pub fn write(fd: u8, buf: &[u8]) -> isize {
+    fault_in(buf);
     x86_64::instructions::interrupts::without_interrupts(|| {
         get_process_manager().current().read().write(fd, buf)
     })
@@ -165,6 +290,71 @@ pub fn exit(ret: isize, context: &mut ProcessContext) {
         manager.switch_next(context);
     })
 }
+/// Kill current process by `signal` and switch to the next process
+pub fn kill_by_signal(signal: Signal, reason: core::fmt::Arguments, context: &mut ProcessContext) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let manager = get_process_manager();
+        let current = manager.current();
+
+        warn!(
+            "Process {}#{} killed by {:?}: {} at {:#x}",
+            current.read().name(),
+            current.pid(),
+            signal,
+            reason,
+            context.stack_frame.instruction_pointer
+        );
+
+        let inner = current.read();
+        let rip = context.stack_frame.instruction_pointer.as_u64();
+        inner.vm().print_backtrace(rip, context.regs.rbp as u64);
+        coredump::dump(&inner, current.pid(), signal, context);
+        drop(inner);
+
+        manager.kill_by_signal(current.pid(), signal);
+        manager.switch_next(context);
+    })
+}
+/// Ask all user processes to terminate, return the count of them
+pub fn request_terminate() -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().request_terminate()
+    })
+}
+
+/// Kill all user processes by `signal`, return the count of them
+pub fn kill_all(signal: Signal) -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().kill_all(signal))
+}
+
+/// Whether the current process was asked to terminate
+pub fn is_terminating() -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().read().is_terminating()
+    })
+}
+
+/// Count of alive user processes
+pub fn alive_count() -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().alive_count())
+}
+
+pub fn has_ready() -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().has_ready())
+}
+
+/// Copy the arguments of the current process into `buf`, return their length
+pub fn args(buf: &mut [u8]) -> usize {
+    // copied out first, `buf` may fault while the process is locked
+    let args = x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().read().args().to_string()
+    });
+
+    let len = args.len().min(buf.len());
+    buf[..len].copy_from_slice(&args.as_bytes()[..len]);
+    args.len()
+}
+
 pub fn get_current_pid() -> usize {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let pid = get_process_manager().current().pid();
@@ -176,14 +366,23 @@ pub fn wait_pid(pid: ProcessId, context: &mut ProcessContext) {
         let manager = get_process_manager();
         if let Some(ret) = manager.get_exit_code(pid) {
             context.set_rax(ret as usize);
+        } else if !manager.wait_pid(pid) {
+            context.set_rax(-1isize as usize);
         } else {
-            manager.wait_pid(pid);
             manager.save_current(context);
             manager.current().write().block();
             manager.switch_next(context);
         }
     })
 }
+pub fn exit_code(pid: ProcessId) -> Option<isize> {
+    get_process_manager().get_exit_code(pid)
+}
+
+/// The signal which killed the process, `None` if it exited by itself
+pub fn killed_by(pid: ProcessId) -> Option<Signal> {
+    x86_64::instructions::interrupts::without_interrupts(|| get_process_manager().killed_by(pid))
+}
 #[inline]
 pub fn still_alive(pid: ProcessId) -> bool {
     x86_64::instructions::interrupts::without_interrupts(|| {
@@ -196,12 +395,78 @@ pub fn fork(context: &mut ProcessContext) {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
         manager.save_current(context);
-        manager.fork();
         let parent = manager.current();
+        if manager.fork().is_none() {
+            parent.write().set_rax(usize::MAX);
+        }
         manager.push_ready(parent.pid());
         manager.switch_next(context);
     })
 }
+/// Account the cycles since current process entered the kernel as user time
+pub fn enter_kernel() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let now = clock::read_tsc();
+        get_process_manager().current().write().enter_kernel(now);
+    })
+}
+/// Account the cycles spent in the kernel as system time of current process
+pub fn leave_kernel() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let now = clock::read_tsc();
+        get_process_manager().current().write().leave_kernel(now);
+    })
+}
+/// Get the CPU times of the process, `ProcessId(0)` means the current process
+pub fn times(pid: ProcessId) -> Option<ProcessTimes> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let manager = get_process_manager();
+        let pid = if pid.0 == 0 {
+            manager.current().pid()
+        } else {
+            pid
+        };
+        manager.times(pid)
+    })
+}
+pub fn is_traced() -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().read().is_traced()
+    })
+}
+/// Set the trace flag of the process, `ProcessId(0)` means the current process
+///
+/// Only the current process and its descendants can be traced.
+pub fn set_traced(pid: ProcessId, traced: bool) -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let manager = get_process_manager();
+        let current = manager.current().pid();
+        let pid = if pid.0 == 0 { current } else { pid };
+        manager.is_descendant(pid, current) && manager.set_traced(pid, traced)
+    })
+}
+pub fn rlimit(res: Rlimit) -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().read().rlimit(res)
+    })
+}
+/// Set the limit of current process, limits can only be lowered
+pub fn set_rlimit(res: Rlimit, val: usize) -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().write().set_rlimit(res, val)
+    })
+}
+/// Account heap allocation of current process, fail if the limit is exceeded
+pub fn charge_heap(size: usize) -> bool {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().write().charge_heap(size)
+    })
+}
+pub fn uncharge_heap(size: usize) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        get_process_manager().current().write().uncharge_heap(size)
+    })
+}
 pub fn new_sem(key: u32, val: usize) -> bool {
     x86_64::instructions::interrupts::without_interrupts(|| {
         let manager = get_process_manager();
@@ -1,8 +1,12 @@
 use super::*;
+use crate::interrupt::clock;
 use crate::memory::*;
 use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use spin::*;
+use syscall_def::info::{ProcessInfo, ProcessTimes, name_buf};
+use syscall_def::limit::Rlimit;
+use x86_64::registers::model_specific::FsBase;
 use x86_64::structures::paging::mapper::MapToError;
 use x86_64::structures::paging::page::PageRange;
 use x86_64::structures::paging::*;
@@ -18,8 +22,18 @@ pub struct ProcessInner {
     parent: Option<Weak<Process>>,
     children: Vec<Arc<Process>>,
     ticks_passed: usize,
+    times: ProcessTimes,
+    /// TSC of the last change between user and kernel mode
+    stamp: u64,
+    traced: bool,
+    /// termination requested, polled by the process with `Syscall::Terminating`
+    terminating: bool,
+    /// the signal which killed the process
+    signal: Option<Signal>,
     status: ProgramStatus,
     context: ProcessContext,
+    /// FS base, the thread pointer of the process
+    fs_base: VirtAddr,
     exit_code: Option<isize>,
     proc_data: Option<ProcessData>,
     proc_vm: Option<ProcessVm>,
@@ -41,6 +55,11 @@ impl Process {
         self.inner.read()
     }
 
+    #[inline]
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<ProcessInner>> {
+        self.inner.try_write()
+    }
+
     pub fn new(
         name: String,
         parent: Option<Weak<Process>>,
@@ -52,13 +71,23 @@ impl Process {
         // create context
         let pid = ProcessId::new();
         let proc_vm = proc_vm.unwrap_or_else(|| ProcessVm::new(PageTableContext::new()));
+        let now = clock::read_tsc();
 
         let inner = ProcessInner {
             name,
             parent,
             status: ProgramStatus::Ready,
             context: ProcessContext::default(),
+            fs_base: VirtAddr::zero(),
             ticks_passed: 0,
+            times: ProcessTimes {
+                start: now,
+                ..Default::default()
+            },
+            stamp: now,
+            traced: false,
+            terminating: false,
+            signal: None,
             exit_code: None,
             children: Vec::new(),
             proc_vm: Some(proc_vm),
@@ -87,6 +116,20 @@ impl Process {
         inner.kill(ret);
     }
 
+    pub fn info(&self) -> ProcessInfo {
+        let inner = self.inner.read();
+
+        ProcessInfo {
+            pid: self.pid.0,
+            ppid: inner.parent().map(|p| p.pid.0).unwrap_or(0),
+            status: inner.status.into(),
+            name: name_buf(&inner.name),
+            ticks: inner.ticks_passed,
+            memory: inner.proc_vm.as_ref().map_or(0, |vm| vm.memory_usage()) as usize,
+            times: inner.times(clock::read_tsc()),
+        }
+    }
+
     pub fn alloc_init_stack(&self) -> VirtAddr {
         // 分配并初始化进程的栈空间，返回栈顶地址
         let stack_top = self.write().vm_mut().init_proc_stack(self.pid);
@@ -100,10 +143,10 @@ impl Process {
 
         stack_top
     }
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+    pub fn fork(self: &Arc<Self>) -> Result<Arc<Self>, elf::ElfLoadError> {
         let mut inner = self.inner.write();
-        let child_inner = inner.fork(Arc::downgrade(self));
         let child_pid = ProcessId::new();
+        let child_inner = inner.fork(Arc::downgrade(self), child_pid)?;
         debug!(
             "Forking process {}#{} to {}#{}",
             inner.name(),
@@ -120,7 +163,7 @@ impl Process {
         inner.context.set_rax(child.pid.0 as usize);
         // child.write().pause();
         inner.pause();
-        child
+        Ok(child)
     }
 }
 
@@ -133,6 +176,57 @@ impl ProcessInner {
         self.ticks_passed += 1;
     }
 
+    /// Account the cycles since the last stamp as user time
+    pub fn enter_kernel(&mut self, now: u64) {
+        self.times.user += now.saturating_sub(self.stamp);
+        self.stamp = now;
+    }
+
+    /// Account the cycles since the last stamp as system time
+    pub fn leave_kernel(&mut self, now: u64) {
+        self.times.system += now.saturating_sub(self.stamp);
+        self.stamp = now;
+    }
+
+    /// Add the times of an exited child and its children
+    pub fn add_child_times(&mut self, child: &ProcessTimes) {
+        self.times.children_user += child.user + child.children_user;
+        self.times.children_system += child.system + child.children_system;
+    }
+
+    pub fn times(&self, now: u64) -> ProcessTimes {
+        ProcessTimes { now, ..self.times }
+    }
+
+    pub fn is_traced(&self) -> bool {
+        self.traced
+    }
+
+    pub fn set_traced(&mut self, traced: bool) {
+        self.traced = traced;
+    }
+
+    pub fn is_terminating(&self) -> bool {
+        self.terminating
+    }
+
+    /// Ask the process to terminate
+    ///
+    /// NOTE: processes cannot handle signals yet, they poll the request
+    /// and exit by themselves, or are killed after a grace period.
+    pub fn request_terminate(&mut self) {
+        self.terminating = true;
+    }
+
+    /// The signal which killed the process, `None` if it exited
+    pub fn signal(&self) -> Option<Signal> {
+        self.signal
+    }
+
+    pub fn set_signal(&mut self, signal: Signal) {
+        self.signal = Some(signal);
+    }
+
     pub fn status(&self) -> ProgramStatus {
         self.status
     }
@@ -173,8 +267,28 @@ impl ProcessInner {
     //     self.context.as_mut().as_mut_ptr()
     // }
 
+    /// The context saved when the process was switched out
+    pub fn context(&self) -> &ProcessContext {
+        &self.context
+    }
+
+    pub fn fs_base(&self) -> VirtAddr {
+        self.fs_base
+    }
+
     pub fn handle_page_fault(&mut self, addr: VirtAddr) -> bool {
-        self.vm_mut().handle_page_fault(addr)
+        let max_stack = self.rlimit(Rlimit::Stack) as u64;
+        self.vm_mut().handle_page_fault(addr, max_stack)
+    }
+
+    pub fn is_stack_overflow(&self, addr: VirtAddr) -> bool {
+        let max_stack = self.rlimit(Rlimit::Stack) as u64;
+        self.vm().is_stack_overflow(addr, max_stack)
+    }
+
+    /// Check if the process has run out of its CPU ticks
+    pub fn cpu_exhausted(&self) -> bool {
+        self.ticks_passed > self.rlimit(Rlimit::Cpu)
     }
 
     /// Save the process's context
@@ -184,6 +298,7 @@ impl ProcessInner {
             return;
         }
         self.context.save(context);
+        self.fs_base = FsBase::read();
         self.pause();
     }
 
@@ -192,7 +307,10 @@ impl ProcessInner {
     pub(super) fn restore(&mut self, context: &mut ProcessContext) {
         self.context.restore(context);
         self.proc_vm.as_ref().unwrap().page_table.load();
+        FsBase::write(self.fs_base);
         self.resume();
+        // time spent off the cpu is not accounted
+        self.stamp = clock::read_tsc();
     }
 
     pub fn parent(&self) -> Option<Arc<Process>> {
@@ -215,35 +333,43 @@ impl ProcessInner {
 
     pub fn load_elf(
         &mut self,
-        elf: &ElfFile,
+        elf: &ElfFile<'static>,
         mapper: x86_64::structures::paging::OffsetPageTable<'static>,
         pid: ProcessId,
-    ) {
+    ) -> Result<(), elf::ElfLoadError> {
         // let alloc = &mut *get_frame_alloc_for_sure();
         // 使用ProcessVm的load_elf函数加载ELF文件并初始化栈
-        let stack_top = self
-            .proc_vm
-            .as_mut()
-            .unwrap()
-            .load_elf(elf, mapper, pid)
-            .unwrap();
+        let (stack_top, entry) = self.proc_vm.as_mut().unwrap().load_elf(elf, mapper, pid)?;
         debug!(
-            "Process {}#{} ELF loaded at {:#x}",
+            "Process {}#{} ELF loaded, stack top: {:#x}, entry: {:#x}",
             self.name,
             pid,
-            stack_top.as_u64()
+            stack_top.as_u64(),
+            entry.as_u64()
         );
 
         // 设置栈帧
-        self.set_stack_frame(VirtAddr::new(elf.header.pt2.entry_point()), stack_top);
+        self.set_stack_frame(entry, stack_top);
+        self.fs_base = self.vm().fs_base();
+
+        Ok(())
     }
-    pub fn fork(&mut self, parent: Weak<Process>) -> ProcessInner {
+    pub fn fork(
+        &mut self,
+        parent: Weak<Process>,
+        pid: ProcessId,
+    ) -> Result<ProcessInner, elf::ElfLoadError> {
         let stack_offset_count = self.children.len() as u64 + 1;
-        let child_vm = self.proc_vm.as_ref().unwrap().fork(stack_offset_count);
+        let child_vm = self
+            .proc_vm
+            .as_ref()
+            .unwrap()
+            .fork(stack_offset_count, pid)?;
         let mut child_context: ProcessContext = self.context.clone();
         let parent_bottom = self.proc_vm.as_ref().unwrap().stack_bot();
         let child_vm = Some(child_vm);
         let child_bottom = child_vm.as_ref().unwrap().stack_bot();
+        let child_fs_base = child_vm.as_ref().unwrap().fs_base();
         let offset = child_bottom - parent_bottom;
 
         child_context.offset_rsp(offset);
@@ -252,18 +378,29 @@ impl ProcessInner {
         // 克隆进程数据结构
         let child_data = self.proc_data.clone();
 
+        let now = clock::read_tsc();
+
         // 构造子进程的内部结构
-        ProcessInner {
+        Ok(ProcessInner {
             name: self.name.clone(),
             parent: Some(parent),
             children: Vec::new(),
             ticks_passed: 0, // 子进程从0开始计时
+            times: ProcessTimes {
+                start: now,
+                ..Default::default()
+            },
+            stamp: now,
+            traced: self.traced,
+            terminating: false,
+            signal: None,
             status: ProgramStatus::Ready,
             context: child_context,
+            fs_base: child_fs_base,
             exit_code: None,
             proc_data: child_data,
             proc_vm: child_vm,
-        }
+        })
     }
     pub fn set_rax(&mut self, ret: usize) {
         self.context.set_rax(ret);
@@ -317,6 +454,8 @@ impl core::fmt::Debug for Process {
             .field("parent", &inner.parent().map(|p| p.pid))
             .field("status", &inner.status)
             .field("ticks_passed", &inner.ticks_passed)
+            .field("times", &inner.times)
+            .field("traced", &inner.traced)
             .field("children", &inner.children.iter().map(|c| c.pid.0))
             .field("status", &inner.status)
             .field("context", &inner.context)
@@ -1,18 +1,26 @@
-use alloc::format;
+use alloc::{format, vec::Vec};
+use core::ops::Range;
 use x86_64::{
     VirtAddr,
-    structures::paging::{mapper::MapToError, page::*, *},
+    structures::paging::{mapper::TranslateResult, page::*, *},
 };
 
 use crate::{humanized_size, memory::*};
-use xmas_elf::ElfFile;
+use xmas_elf::{ElfFile, program};
 
+pub mod region;
 pub mod stack;
+pub mod tls;
 
-use self::stack::*;
+use self::{region::*, stack::*, tls::*};
 
 use super::{PageTableContext, ProcessId};
 
+/// Position-independent executables are loaded above this address
+pub const USER_PIE_BASE: u64 = 0x1000_0000_0000;
+/// The load base is randomised within this count of pages
+const USER_PIE_ASLR_PAGES: u64 = 0x10000;
+
 type MapperRef<'a> = &'a mut OffsetPageTable<'static>;
 type FrameAllocatorRef<'a> = &'a mut BootInfoFrameAllocator;
 
@@ -22,6 +30,20 @@ pub struct ProcessVm {
 
     // stack is pre-process allocated
     pub(super) stack: Stack,
+
+    // loaded segments of executables, mapped on page fault, PIEs are
+    // loaded eagerly as their relocations are applied to the pages
+    pub(super) regions: Vec<ElfRegion>,
+
+    // count of pages mapped for the regions
+    pub(super) region_pages: u64,
+
+    // thread-local storage from PT_TLS
+    pub(super) tls: Option<Tls>,
+
+    // symbols of the loaded ELF and its load base, for backtraces
+    pub(super) symbols: Option<elf::SymbolTable<'static>>,
+    pub(super) load_base: u64,
 }
 
 impl ProcessVm {
@@ -29,6 +51,11 @@ impl ProcessVm {
         Self {
             page_table,
             stack: Stack::empty(),
+            regions: Vec::new(),
+            region_pages: 0,
+            tls: None,
+            symbols: None,
+            load_base: 0,
         }
     }
 
@@ -41,10 +68,12 @@ impl ProcessVm {
     pub fn init_proc_stack(&mut self, pid: ProcessId) -> VirtAddr {
         // 计算基于PID的栈顶地址
         // 栈顶位置 = STACK_MAX - (pid-1) * STACK_MAX_SIZE - 8
-        use self::stack::{STACK_DEF_PAGE, STACK_MAX, STACK_MAX_SIZE};
+        use self::stack::{STACK_ASLR_PAGES, STACK_DEF_PAGE, STACK_MAX, STACK_MAX_SIZE};
 
-        let stack_top = STACK_INIT_TOP - ((pid.0 as u64 - 1) * STACK_MAX_SIZE);
-        let stack_bot = STACK_INIT_BOT - ((pid.0 as u64 - 1) * STACK_MAX_SIZE);
+        // the stack top is randomised within its region
+        let offset = aslr::offset(STACK_ASLR_PAGES);
+        let stack_top = STACK_INIT_TOP - ((pid.0 as u64 - 1) * STACK_MAX_SIZE) - offset;
+        let stack_bot = STACK_INIT_BOT - ((pid.0 as u64 - 1) * STACK_MAX_SIZE) - offset;
         // let stack_bot = stack_top - STACK_DEF_PAGE * crate::memory::PAGE_SIZE + 1;
 
         let stack_top_addr = VirtAddr::new(stack_top);
@@ -82,51 +111,200 @@ impl ProcessVm {
         stack_top_addr
     }
 
-    pub fn handle_page_fault(&mut self, addr: VirtAddr) -> bool {
+    /// Handle a page fault, the stack may grow up to `max_stack` bytes
+    pub fn handle_page_fault(&mut self, addr: VirtAddr, max_stack: u64) -> bool {
+        if self.regions.iter().any(|r| r.contains(addr.as_u64())) {
+            return self.load_page(addr);
+        }
+
         let mapper = &mut self.page_table.mapper();
         let alloc = &mut *get_frame_alloc_for_sure();
 
-        self.stack.handle_page_fault(addr, mapper, alloc)
+        self.stack.handle_page_fault(addr, mapper, alloc, max_stack)
+    }
+
+    /// Map the page containing `addr` and fill it from the loaded segments
+    fn load_page(&mut self, addr: VirtAddr) -> bool {
+        let page = Page::<Size4KiB>::containing_address(addr);
+        let page_start = page.start_address().as_u64();
+        let page_end = page_start + PAGE_SIZE;
+
+        let mapper = &mut self.page_table.mapper();
+        let alloc = &mut *get_frame_alloc_for_sure();
+
+        let frame = match alloc.allocate_frame() {
+            Some(frame) => frame,
+            None => return false,
+        };
+
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(
+                physical_to_virtual(frame.start_address().as_u64()) as *mut u8,
+                PAGE_SIZE as usize,
+            )
+        };
+
+        // zero the whole page, which covers .bss
+        buf.fill(0);
+
+        // the page may be shared by several segments
+        let mut flags: Option<PageTableFlags> = None;
+        for region in self.regions.iter() {
+            if region.overlaps(page_start, page_end) {
+                region.fill(page_start, buf);
+                flags = Some(match flags {
+                    Some(flags) => elf::merge_flags(flags, region.flags()),
+                    None => region.flags(),
+                });
+            }
+        }
+
+        let flags = flags.unwrap_or(PageTableFlags::PRESENT | PageTableFlags::NO_EXECUTE);
+
+        trace!("Loading page {:#x} with flags {:?}", page_start, flags);
+
+        match unsafe { mapper.map_to(page, frame, flags, alloc) } {
+            Ok(flush) => flush.flush(),
+            Err(e) => {
+                error!("Failed to map page {:#x}: {:?}", page_start, e);
+                unsafe { alloc.deallocate_frame(frame) };
+                return false;
+            }
+        }
+
+        self.region_pages += 1;
+        true
+    }
+
+    pub fn is_stack_overflow(&self, addr: VirtAddr, max_stack: u64) -> bool {
+        self.stack.is_overflow(addr, max_stack)
     }
 
     pub fn load_elf(
         &mut self,
-        elf: &ElfFile,
+        elf: &ElfFile<'static>,
         mut mapper: x86_64::structures::paging::OffsetPageTable<'static>,
         pid: ProcessId,
-    ) -> Result<VirtAddr, MapToError<Size4KiB>> {
+    ) -> Result<(VirtAddr, VirtAddr), elf::ElfLoadError> {
         // 初始化进程栈并获取栈顶地址
         let stack_top = self.init_proc_stack(pid);
         // 获取页表映射器和帧分配器
         let frame_allocator = &mut *get_frame_alloc_for_sure();
 
-        // 加载ELF文件到内存，设置为用户可访问
-        elf::load_elf(
-            elf,
-            *PHYSICAL_OFFSET.get().unwrap(),
-            &mut mapper,
-            frame_allocator,
-            true, // 设置USER_ACCESSIBLE标志
-        )?;
+        let reserved = reserved_ranges(pid);
+
+        // position-independent executables are loaded at a random base
+        let base = if elf::is_pie(elf) {
+            USER_PIE_BASE + aslr::offset(USER_PIE_ASLR_PAGES)
+        } else {
+            0
+        };
+
+        if base != 0 {
+            // relocations are applied to the pages, so load them now
+            elf::load_elf_at(
+                elf,
+                base,
+                &reserved,
+                *PHYSICAL_OFFSET.get().unwrap(),
+                &mut mapper,
+                frame_allocator,
+                true, // 设置USER_ACCESSIBLE标志
+            )?;
+        } else {
+            // segments are loaded on page fault
+            elf::validate(elf, base, &reserved, true)?;
+
+            for segment in elf.program_iter() {
+                if segment.get_type() == Ok(program::Type::Load) && segment.mem_size() != 0 {
+                    self.regions.push(ElfRegion::new(elf, &segment, base));
+                }
+            }
+        }
 
-        // 返回栈顶地址
-        Ok(stack_top)
+        if let Some(template) = elf::tls_template(elf)? {
+            self.tls = Some(Tls::new(template, pid, &mut mapper, frame_allocator)?);
+        }
+
+        self.symbols = elf::SymbolTable::from_elf(elf);
+        self.load_base = base;
+
+        let entry = VirtAddr::new(base + elf.header.pt2.entry_point());
+
+        // 返回栈顶地址和入口地址
+        Ok((stack_top, entry))
     }
 
     pub(super) fn memory_usage(&self) -> u64 {
         self.stack.memory_usage()
+            + self.region_pages * PAGE_SIZE
+            + self.tls.as_ref().map_or(0, |tls| tls.memory_usage())
     }
-    pub fn fork(&self, stack_offset_count: u64) -> Self {
+
+    /// The FS base of the process, which points to its TLS
+    pub fn fs_base(&self) -> VirtAddr {
+        self.tls
+            .as_ref()
+            .map_or(VirtAddr::zero(), |tls| tls.thread_pointer())
+    }
+    pub fn fork(&self, stack_offset_count: u64, pid: ProcessId) -> Result<Self, elf::ElfLoadError> {
         // clone the page table context (see instructions)
         let owned_page_table = self.page_table.fork();
 
         let mapper = &mut owned_page_table.mapper();
         let alloc = &mut *get_frame_alloc_for_sure();
 
-        Self {
+        // before the stack, which cannot fail to be mapped
+        let tls = match &self.tls {
+            Some(tls) => Some(tls.fork(pid, mapper, alloc)?),
+            None => None,
+        };
+
+        Ok(Self {
             page_table: owned_page_table,
             stack: self.stack.fork(mapper, alloc, stack_offset_count),
+            regions: self.regions.clone(),
+            // loaded pages are shared with the parent
+            region_pages: 0,
+            tls,
+            symbols: self.symbols,
+            load_base: self.load_base,
+        })
+    }
+
+    /// Mapped user pages of the loaded segments, stack and TLS
+    ///
+    /// sorted by address, with their page table flags
+    pub fn mapped_pages(&self) -> Vec<(Page, PageTableFlags)> {
+        let mut ranges: Vec<(u64, u64)> =
+            self.regions.iter().map(|r| (r.start(), r.end())).collect();
+        ranges.push((self.stack.stack_bot(), self.stack.stack_top()));
+        ranges.extend(self.tls.as_ref().map(|tls| tls.range()));
+
+        let mapper = self.page_table.mapper();
+        let mut pages = Vec::new();
+
+        for (start, end) in ranges.into_iter().filter(|(start, end)| start < end) {
+            let first = Page::<Size4KiB>::containing_address(VirtAddr::new(start));
+            let last = Page::containing_address(VirtAddr::new(end - 1));
+
+            for page in Page::range_inclusive(first, last) {
+                if let TranslateResult::Mapped { flags, .. } =
+                    mapper.translate(page.start_address())
+                {
+                    pages.push((page, flags));
+                }
+            }
         }
+
+        pages.sort_by_key(|(page, _)| *page);
+        pages.dedup_by_key(|(page, _)| *page);
+        pages
+    }
+
+    /// Print the backtrace of the process, its page table must be active
+    pub fn print_backtrace(&self, rip: u64, rbp: u64) {
+        crate::utils::backtrace::print_user(rip, rbp, self.symbols.as_ref(), self.load_base);
     }
 
     pub fn stack_bot(&self) -> u64 {
@@ -134,6 +312,20 @@ impl ProcessVm {
     }
 }
 
+/// Ranges of the lower half which the kernel maps for `pid`
+///
+/// the stack region of the pid, the TLS slots and the user heap, which is
+/// mapped in every address space
+fn reserved_ranges(pid: ProcessId) -> [Range<u64>; 3] {
+    let stack_top = STACK_MAX - (pid.0 as u64 - 1) * STACK_MAX_SIZE;
+
+    [
+        stack_top - STACK_MAX_SIZE..stack_top,
+        USER_TLS_START..USER_TLS_END,
+        user::USER_HEAP_START as u64..user::USER_HEAP_END as u64,
+    ]
+}
+
 impl core::fmt::Debug for ProcessVm {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         let (size, unit) = humanized_size(self.memory_usage());
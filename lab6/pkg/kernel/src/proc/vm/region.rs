@@ -0,0 +1,72 @@
+use x86_64::structures::paging::PageTableFlags;
+use xmas_elf::{ElfFile, program::ProgramHeader};
+
+/// A `PT_LOAD` segment whose pages are filled on demand
+///
+/// NOTE: only apps loaded by the bootloader can be spawned, so the file
+/// data is always taken from their ELF image in memory. Reading pages
+/// from a file on the FAT16 root filesystem is not implemented.
+#[derive(Clone)]
+pub struct ElfRegion {
+    /// first address of the segment
+    start: u64,
+    /// end of the segment in memory, pages up to here are zero filled
+    end: u64,
+    /// file data of the segment, mapped from `start`
+    data: &'static [u8],
+    flags: PageTableFlags,
+}
+
+impl ElfRegion {
+    /// The segment must have been checked by `elf::validate`
+    pub fn new(elf: &ElfFile<'static>, segment: &ProgramHeader, base: u64) -> Self {
+        let input: &'static [u8] = elf.input;
+        let offset = segment.offset() as usize;
+        let start = base + segment.virtual_addr();
+
+        Self {
+            start,
+            end: start + segment.mem_size(),
+            data: &input[offset..offset + segment.file_size() as usize],
+            flags: elf::segment_flags(segment, true),
+        }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// Whether the region overlaps with `[start, end)`
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    pub fn flags(&self) -> PageTableFlags {
+        self.flags
+    }
+
+    /// Copy the file data lying in the page at `page_start` into `page`
+    ///
+    /// the data is copied from the ELF image in memory, never read from
+    /// a file; the rest of the page is left untouched
+    pub fn fill(&self, page_start: u64, page: &mut [u8]) {
+        let file_end = self.start + self.data.len() as u64;
+        let copy_start = page_start.max(self.start);
+        let copy_end = (page_start + page.len() as u64).min(file_end);
+
+        if copy_start < copy_end {
+            let src = (copy_start - self.start) as usize;
+            let dst = (copy_start - page_start) as usize;
+            let len = (copy_end - copy_start) as usize;
+            page[dst..dst + len].copy_from_slice(&self.data[src..src + len]);
+        }
+    }
+}
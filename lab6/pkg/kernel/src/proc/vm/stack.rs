@@ -19,6 +19,8 @@ pub const STACK_DEF_SIZE: u64 = STACK_DEF_PAGE * crate::memory::PAGE_SIZE;
 
 pub const STACK_INIT_BOT: u64 = STACK_MAX - STACK_DEF_SIZE;
 pub const STACK_INIT_TOP: u64 = STACK_MAX - 8;
+/// The stack top is randomised within this count of pages
+pub const STACK_ASLR_PAGES: u64 = 0x4000;
 
 const STACK_INIT_TOP_PAGE: Page<Size4KiB> = Page::containing_address(VirtAddr::new(STACK_INIT_TOP));
 
@@ -76,12 +78,13 @@ impl Stack {
         addr: VirtAddr,
         mapper: MapperRef,
         alloc: FrameAllocatorRef,
+        max_size: u64,
     ) -> bool {
         if !self.is_on_stack(addr) {
             return false;
         }
 
-        if let Err(m) = self.grow_stack(addr, mapper, alloc) {
+        if let Err(m) = self.grow_stack(addr, mapper, alloc, max_size) {
             error!("Grow stack failed: {:?}", m);
             return false;
         }
@@ -89,6 +92,22 @@ impl Stack {
         true
     }
 
+    /// Check if `addr` is on the stack but beyond its growth limit
+    pub fn is_overflow(&self, addr: VirtAddr, max_size: u64) -> bool {
+        self.is_on_stack(addr) && addr.as_u64() < self.limit_bot(max_size)
+    }
+
+    /// The lowest address the stack may grow to
+    ///
+    /// The lowest page of the stack region is never mapped and acts as
+    /// a guard page, so the stack cannot run into its neighbour.
+    fn limit_bot(&self, max_size: u64) -> u64 {
+        let top = self.range.end.start_address().as_u64();
+        let guard = self.range.start.start_address().as_u64() & STACK_START_MASK;
+        top.saturating_sub(max_size)
+            .max(guard + crate::memory::PAGE_SIZE)
+    }
+
     fn is_on_stack(&self, addr: VirtAddr) -> bool {
         let addr = addr.as_u64();
         let cur_stack_bot = self.range.start.start_address().as_u64();
@@ -102,6 +121,7 @@ impl Stack {
         addr: VirtAddr,
         mapper: MapperRef,
         alloc: FrameAllocatorRef,
+        max_size: u64,
     ) -> Result<(), MapToError<Size4KiB>> {
         debug_assert!(self.is_on_stack(addr), "Address is not on stack.");
 
@@ -114,10 +134,9 @@ impl Stack {
 
         let current_base = self.range.start.start_address().as_u64();
         let new_base = page.start_address().as_u64();
-        let stack_size = self.range.end.start_address().as_u64() - new_base;
 
-        if stack_size > STACK_MAX_SIZE {
-            error!("Stack overflow: attempted to grow beyond maximum size");
+        if new_base < self.limit_bot(max_size) {
+            error!("Stack overflow: {:#x} is beyond the stack limit", addr);
             return Err(MapToError::FrameAllocationFailed);
         }
 
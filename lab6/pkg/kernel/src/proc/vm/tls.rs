@@ -0,0 +1,144 @@
+use elf::{ElfLoadError, TlsTemplate};
+use x86_64::{VirtAddr, structures::paging::Translate};
+
+use super::{FrameAllocatorRef, MapperRef};
+use crate::memory::*;
+use crate::proc::ProcessId;
+
+/// TLS blocks of user processes are placed in per-pid slots from here,
+/// above the shared heap and the stacks, which grow down from `STACK_MAX`
+pub const USER_TLS_START: u64 = 0x5000_0000_0000;
+/// Size of the TLS slot of each process
+pub const TLS_SLOT_SIZE: u64 = 0x100_0000; // 16 MiB
+/// End of the slots of all possible pids
+pub const USER_TLS_END: u64 = USER_TLS_START + u16::MAX as u64 * TLS_SLOT_SIZE;
+
+/// Size of the thread control block, the first word points to itself
+const TCB_SIZE: u64 = 64;
+
+/// Thread-local storage of a process
+///
+/// x86_64 uses the variant II layout: the TLS block sits right below
+/// the thread pointer, which is loaded into FS base and points to the TCB.
+pub struct Tls {
+    template: TlsTemplate<'static>,
+    /// first address of the mapped pages
+    start: u64,
+    pages: u64,
+}
+
+impl Tls {
+    /// Map a TLS block for `pid` and fill it from the template
+    ///
+    /// the page table of `mapper` does not need to be active
+    pub fn new(
+        template: TlsTemplate<'static>,
+        pid: ProcessId,
+        mapper: MapperRef,
+        alloc: FrameAllocatorRef,
+    ) -> Result<Self, ElfLoadError> {
+        if template.align > PAGE_SIZE {
+            return Err(ElfLoadError::InvalidSegment(
+                "TLS alignment exceeds page size",
+            ));
+        }
+
+        let tls = Self::map(template, pid, mapper, alloc)?;
+        let tp = tls.thread_pointer().as_u64();
+
+        // zero the pages, which covers .tbss
+        for i in 0..tls.pages {
+            let page = tls.start + i * PAGE_SIZE;
+            unsafe { core::ptr::write_bytes(user_ptr(mapper, page), 0, PAGE_SIZE as usize) };
+        }
+
+        write_user(mapper, tp - template.block_size(), template.data);
+        write_user(mapper, tp, &tp.to_ne_bytes());
+
+        trace!("TLS mapped at {:#x}, thread pointer: {:#x}", tls.start, tp);
+
+        Ok(tls)
+    }
+
+    /// Allocate a TLS block for the child `pid`, copied from the current one
+    ///
+    /// the page table is shared with the parent and must be active
+    pub fn fork(
+        &self,
+        pid: ProcessId,
+        mapper: MapperRef,
+        alloc: FrameAllocatorRef,
+    ) -> Result<Self, ElfLoadError> {
+        let child = Self::map(self.template, pid, mapper, alloc)?;
+        let tp = child.thread_pointer().as_u64();
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                self.start as *const u8,
+                child.start as *mut u8,
+                (self.pages * PAGE_SIZE) as usize,
+            );
+            (tp as *mut u64).write(tp);
+        }
+
+        Ok(child)
+    }
+
+    /// Map the pages of a TLS block at the start of the slot of `pid`
+    fn map(
+        template: TlsTemplate<'static>,
+        pid: ProcessId,
+        mapper: MapperRef,
+        alloc: FrameAllocatorRef,
+    ) -> Result<Self, ElfLoadError> {
+        let pages = (template.block_size() + TCB_SIZE).div_ceil(PAGE_SIZE);
+        if pages * PAGE_SIZE > TLS_SLOT_SIZE {
+            return Err(ElfLoadError::InvalidSegment("TLS block exceeds its slot"));
+        }
+
+        let start = USER_TLS_START + (pid.0 as u64 - 1) * TLS_SLOT_SIZE;
+
+        elf::map_range(start, pages, mapper, alloc, true, true)?;
+
+        Ok(Self {
+            template,
+            start,
+            pages,
+        })
+    }
+
+    /// The value of FS base for the process
+    pub fn thread_pointer(&self) -> VirtAddr {
+        VirtAddr::new(self.start + self.template.block_size())
+    }
+
+    /// Range of the mapped pages
+    pub fn range(&self) -> (u64, u64) {
+        (self.start, self.start + self.pages * PAGE_SIZE)
+    }
+
+    pub fn memory_usage(&self) -> u64 {
+        self.pages * PAGE_SIZE
+    }
+}
+
+/// Kernel pointer to a mapped user address of `mapper`
+fn user_ptr(mapper: MapperRef, addr: u64) -> *mut u8 {
+    let phys = mapper
+        .translate_addr(VirtAddr::new(addr))
+        .expect("TLS page is not mapped");
+    physical_to_virtual(phys.as_u64()) as *mut u8
+}
+
+/// Copy `data` to a mapped user address of `mapper`, page by page
+fn write_user(mapper: MapperRef, addr: u64, data: &[u8]) {
+    let mut copied = 0;
+    while copied < data.len() {
+        let dst = addr + copied as u64;
+        let len = (PAGE_SIZE - dst % PAGE_SIZE).min((data.len() - copied) as u64) as usize;
+        unsafe {
+            core::ptr::copy_nonoverlapping(data[copied..].as_ptr(), user_ptr(mapper, dst), len);
+        }
+        copied += len;
+    }
+}
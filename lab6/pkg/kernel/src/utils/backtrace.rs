@@ -0,0 +1,117 @@
+//! Stack walking along the frame pointer chain, symbolized with ELF symbols
+
+use boot::BootInfo;
+use core::ops::Range;
+use elf::SymbolTable;
+
+use crate::memory::translate_active;
+
+/// Maximum count of frames to walk
+const MAX_FRAMES: usize = 32;
+
+const KERNEL_SPACE: Range<u64> = 0xffff_8000_0000_0000..u64::MAX;
+const USER_SPACE: Range<u64> = 0x1000..elf::USER_SPACE_END;
+
+static KERNEL_SYMBOLS: spin::Once<Option<SymbolTable<'static>>> = spin::Once::new();
+
+/// `(rip, rbp)` of the kernel fault which is going to panic
+static FAULT: spin::Once<(u64, u64)> = spin::Once::new();
+
+pub fn init(boot_info: &'static BootInfo) {
+    let symbols = KERNEL_SYMBOLS.call_once(|| boot_info.kernel_symbols);
+
+    if symbols.is_none() {
+        warn!("Kernel symbols not found, backtraces will not be symbolized.");
+    }
+}
+
+/// Print the kernel backtrace from the caller's frame
+#[inline(always)]
+pub fn print_kernel_here() {
+    let rbp: u64;
+    unsafe { core::arch::asm!("mov {}, rbp", out(reg) rbp) };
+    print_kernel(None, rbp);
+}
+
+/// Start the backtrace of the coming panic at a faulting kernel context
+pub fn set_fault(rip: u64, rbp: u64) {
+    FAULT.call_once(|| (rip, rbp));
+}
+
+/// Print the kernel backtrace of a panic, from the fault if there is one
+#[inline(always)]
+pub fn print_panic() {
+    match FAULT.get() {
+        Some(&(rip, rbp)) => print_kernel(Some(rip), rbp),
+        None => print_kernel_here(),
+    }
+}
+
+/// Print the kernel backtrace of an interrupted context
+pub fn print_kernel(rip: Option<u64>, rbp: u64) {
+    let symbols = KERNEL_SYMBOLS.get().copied().flatten();
+    print(rip, rbp, symbols.as_ref(), 0, KERNEL_SPACE);
+}
+
+/// Print the backtrace of a user process loaded at `base`
+///
+/// the page table of the process must be active
+pub fn print_user(rip: u64, rbp: u64, symbols: Option<&SymbolTable>, base: u64) {
+    print(Some(rip), rbp, symbols, base, USER_SPACE);
+}
+
+fn print(rip: Option<u64>, rbp: u64, symbols: Option<&SymbolTable>, base: u64, space: Range<u64>) {
+    println!("Backtrace:");
+
+    let mut index = 0;
+    let mut frame = |addr: u64, is_return: bool| {
+        print_frame(index, addr, is_return, symbols, base);
+        index += 1;
+    };
+
+    if let Some(rip) = rip {
+        frame(rip, false);
+    }
+
+    walk(rbp, &space, |ret| frame(ret, true));
+}
+
+/// Walk the frame pointer chain from `rbp`, calling `f` with each return address
+fn walk(mut rbp: u64, space: &Range<u64>, mut f: impl FnMut(u64)) {
+    for _ in 0..MAX_FRAMES {
+        let readable = |addr: u64| space.contains(&addr) && translate_active(addr).is_some();
+        if rbp % 8 != 0 || !readable(rbp) || !readable(rbp + 8) {
+            break;
+        }
+
+        // [rbp] is the caller's rbp, [rbp + 8] is the return address
+        let (next, ret) = unsafe { (*(rbp as *const u64), *((rbp + 8) as *const u64)) };
+        if ret == 0 {
+            break;
+        }
+
+        f(ret);
+
+        // the stack grows down, so callers' frames are above
+        if next <= rbp {
+            break;
+        }
+        rbp = next;
+    }
+}
+
+fn print_frame(index: usize, addr: u64, is_return: bool, symbols: Option<&SymbolTable>, base: u64) {
+    // a return address may be past the end of the calling function
+    let lookup = addr.wrapping_sub(base).wrapping_sub(is_return as u64);
+
+    match symbols.and_then(|symbols| symbols.lookup(lookup)) {
+        Some((name, offset)) => println!(
+            "  #{:<2} {:#018x} {:#}+{:#x}",
+            index,
+            addr,
+            rustc_demangle::demangle(name),
+            offset + is_return as u64
+        ),
+        None => println!("  #{:<2} {:#018x} <unknown>", index, addr),
+    }
+}
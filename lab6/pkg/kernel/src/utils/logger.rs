@@ -1,38 +1,366 @@
+use alloc::vec;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 use log::{Level, LevelFilter, Metadata, Record};
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::interrupt::clock;
+use crate::utils::params::{Param, ParamValue};
+
+/// Size of the kernel log in bytes
+const LOG_BUF_SIZE: usize = 0x10000;
+
+/// Records longer than this are truncated
+const LINE_SIZE: usize = 256;
+
+/// Maximum count of modules with their own level
+const MAX_MODULES: usize = 16;
+
+/// Maximum length of a module path with its own level
+const MODULE_NAME_SIZE: usize = 48;
+
+/// Prefix of the module paths in this crate, hidden in records
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+
+/// Records up to this level are kept in the log
+static LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Debug as usize);
+
+/// Records up to this level are printed to the console
+static CONSOLE_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Warn as usize);
+
+/// Levels of the log as comma separated `set_level` specs, e.g. `info,proc=trace`
+pub static LOGLEVEL: Param<&str> = Param::new("loglevel", "");
+
+/// Level of records printed to the console
+pub static CONSOLE_LOGLEVEL: Param<LevelFilter> = Param::new("console_loglevel", LevelFilter::Warn);
+
+static LOG_BUF: spin::Mutex<LogBuffer> = spin::Mutex::new(LogBuffer::new());
+
+static MODULES: spin::RwLock<[Option<ModuleLevel>; MAX_MODULES]> =
+    spin::RwLock::new([None; MAX_MODULES]);
 
 pub fn init() {
     static LOGGER: Logger = Logger;
     log::set_logger(&LOGGER).unwrap();
 
-    // FIXME: Configure the logger
-    log::set_max_level(LevelFilter::Debug);
+    update_max_level();
     info!("Logger Initialized.");
 }
 
+/// Apply the levels from the kernel parameters
+pub fn configure() {
+    for spec in LOGLEVEL.get().split(',').filter(|spec| !spec.is_empty()) {
+        if !set_level(spec) {
+            warn!("Invalid log level: {}", spec);
+        }
+    }
+
+    set_console_level(CONSOLE_LOGLEVEL.get());
+}
+
+/// Set the level of the log with a `level` or `module=level` spec
+///
+/// Modules are matched by path, with or without the crate name,
+/// e.g. `proc=trace` also applies to `proc::manager`.
+pub fn set_level(spec: &str) -> bool {
+    let (module, level) = match spec.split_once('=') {
+        Some((module, level)) => (Some(module.trim()), level),
+        None => (None, spec),
+    };
+
+    let Some(level) = parse_level(level.trim()) else {
+        return false;
+    };
+
+    match module {
+        None => LEVEL.store(level as usize, Ordering::Relaxed),
+        Some(module) if !set_module_level(module, level) => return false,
+        _ => {}
+    }
+
+    update_max_level();
+    true
+}
+
+/// Set the level of records printed to the console
+pub fn set_console_level(level: LevelFilter) {
+    CONSOLE_LEVEL.store(level as usize, Ordering::Relaxed);
+    update_max_level();
+}
+
+/// Parse a level by name or by number, see `syscall_def::syslog::LEVEL_NAMES`
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    match level.parse::<usize>() {
+        Ok(n) => level_from_usize(n),
+        Err(_) => level.parse().ok(),
+    }
+}
+
+pub fn level_from_usize(level: usize) -> Option<LevelFilter> {
+    LevelFilter::iter().nth(level)
+}
+
+impl ParamValue for LevelFilter {
+    fn parse(value: &'static str) -> Option<Self> {
+        parse_level(value)
+    }
+}
+
+/// Copy the oldest records into `buf`, return the count of bytes
+///
+/// The records are copied out of the lock first, as a page fault on `buf`
+/// may log records itself.
+pub fn read(buf: &mut [u8], clear: bool) -> usize {
+    let mut records = vec![0; buf.len().min(LOG_BUF_SIZE)];
+    let len = without_interrupts(|| {
+        let mut log = LOG_BUF.lock();
+        let len = log.read(&mut records);
+        if clear {
+            log.clear();
+        }
+        len
+    });
+
+    buf[..len].copy_from_slice(&records[..len]);
+    len
+}
+
+pub fn clear() {
+    without_interrupts(|| LOG_BUF.lock().clear());
+}
+
+/// Size of the records in the log
+pub fn size() -> usize {
+    without_interrupts(|| LOG_BUF.lock().len)
+}
+
+fn set_module_level(module: &str, level: LevelFilter) -> bool {
+    let module = module.strip_prefix(CRATE_PREFIX).unwrap_or(module);
+    if module.is_empty() || module.len() > MODULE_NAME_SIZE {
+        return false;
+    }
+
+    without_interrupts(|| {
+        let mut modules = MODULES.write();
+
+        let slot = modules
+            .iter()
+            .position(|m| m.is_some_and(|m| m.name() == module))
+            .or_else(|| modules.iter().position(Option::is_none));
+
+        match slot {
+            Some(slot) => {
+                modules[slot] = Some(ModuleLevel::new(module, level));
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Let the `log` macros pass records of the most verbose level in use
+fn update_max_level() {
+    let level = MODULES
+        .read()
+        .iter()
+        .flatten()
+        .map(|m| m.level)
+        .fold(load_level(&LEVEL), Ord::max);
+
+    log::set_max_level(level.max(load_level(&CONSOLE_LEVEL)));
+}
+
+fn load_level(level: &AtomicUsize) -> LevelFilter {
+    level_from_usize(level.load(Ordering::Relaxed)).unwrap_or(LevelFilter::Off)
+}
+
+/// Level of records from `target`, the most specific module wins
+fn level_for(target: &str) -> LevelFilter {
+    let path = target.strip_prefix(CRATE_PREFIX).unwrap_or(target);
+
+    let Some(modules) = MODULES.try_read() else {
+        return load_level(&LEVEL);
+    };
+
+    modules
+        .iter()
+        .flatten()
+        .filter(|m| m.matches(path))
+        .max_by_key(|m| m.len)
+        .map_or(load_level(&LEVEL), |m| m.level)
+}
+
+#[derive(Clone, Copy)]
+struct ModuleLevel {
+    name: [u8; MODULE_NAME_SIZE],
+    len: usize,
+    level: LevelFilter,
+}
+
+impl ModuleLevel {
+    fn new(module: &str, level: LevelFilter) -> Self {
+        let mut name = [0; MODULE_NAME_SIZE];
+        name[..module.len()].copy_from_slice(module.as_bytes());
+
+        Self {
+            name,
+            len: module.len(),
+            level,
+        }
+    }
+
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.len]).unwrap_or_default()
+    }
+
+    /// Whether `path` is the module or one of its children
+    fn matches(&self, path: &str) -> bool {
+        match path.strip_prefix(self.name()) {
+            Some(rest) => rest.is_empty() || rest.starts_with("::"),
+            None => false,
+        }
+    }
+}
+
+/// Ring buffer of log lines, the oldest lines are dropped when full
+struct LogBuffer {
+    buf: [u8; LOG_BUF_SIZE],
+    /// offset of the oldest byte
+    head: usize,
+    len: usize,
+}
+
+impl LogBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; LOG_BUF_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn byte(&self, index: usize) -> u8 {
+        self.buf[(self.head + index) % LOG_BUF_SIZE]
+    }
+
+    /// Drop the oldest line
+    fn pop_line(&mut self) {
+        let line = (0..self.len)
+            .position(|i| self.byte(i) == b'\n')
+            .map_or(self.len, |i| i + 1);
+
+        self.head = (self.head + line) % LOG_BUF_SIZE;
+        self.len -= line;
+    }
+
+    fn push(&mut self, line: &[u8]) {
+        while self.len + line.len() > LOG_BUF_SIZE {
+            self.pop_line();
+        }
+
+        for &byte in line {
+            self.buf[(self.head + self.len) % LOG_BUF_SIZE] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn read(&self, buf: &mut [u8]) -> usize {
+        let len = self.len.min(buf.len());
+        for (i, byte) in buf[..len].iter_mut().enumerate() {
+            *byte = self.byte(i);
+        }
+        len
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+/// A log line on the stack, truncated at a char boundary when full
+struct Line {
+    buf: [u8; LINE_SIZE],
+    len: usize,
+}
+
+impl Write for Line {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut len = s.len().min(LINE_SIZE - 1 - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+
+        if len < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 struct Logger;
 
 impl log::Log for Logger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        _metadata.level() <= log::max_level()
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let level = metadata.level();
+        level <= load_level(&CONSOLE_LEVEL) || level <= level_for(metadata.target())
     }
 
     fn log(&self, record: &Record) {
-        // FIXME: Implement the logger with serial output
-        let color_code = match record.level() {
-            Level::Error => "\x1b[31m", // red
-            Level::Warn => "\x1b[33m",  // yellow
-            Level::Info => "\x1b[32m",  // green
-            Level::Debug => "\x1b[36m", // cyan
-            Level::Trace => "\x1b[90m", // black
-        };
-        let reset_code = "\x1b[0m";
-        println!(
-            "{}[{}]{} {}",
-            color_code,
-            record.level(),
-            reset_code,
-            record.args()
-        );
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let uptime = clock::uptime();
+        let module = record.module_path().unwrap_or(record.target());
+        let module = module.strip_prefix(CRATE_PREFIX).unwrap_or(module);
+
+        if record.level() <= level_for(record.target()) {
+            let mut line = Line {
+                buf: [0; LINE_SIZE],
+                len: 0,
+            };
+            let _ = write!(
+                line,
+                "[{:>5}.{:06}] {:<5} {}: {}",
+                uptime.as_secs(),
+                uptime.subsec_micros(),
+                record.level(),
+                module,
+                record.args()
+            );
+            line.buf[line.len] = b'\n';
+
+            // records from a panic in the logger are only printed
+            without_interrupts(|| {
+                if let Some(mut log) = LOG_BUF.try_lock() {
+                    log.push(&line.buf[..=line.len]);
+                }
+            });
+        }
+
+        if record.level() <= load_level(&CONSOLE_LEVEL) {
+            let color_code = match record.level() {
+                Level::Error => "\x1b[31m", // red
+                Level::Warn => "\x1b[33m",  // yellow
+                Level::Info => "\x1b[32m",  // green
+                Level::Debug => "\x1b[36m", // cyan
+                Level::Trace => "\x1b[90m", // black
+            };
+            let reset_code = "\x1b[0m";
+            println!(
+                "{}[{}]{} {}",
+                color_code,
+                record.level(),
+                reset_code,
+                record.args()
+            );
+        }
     }
 
     fn flush(&self) {}
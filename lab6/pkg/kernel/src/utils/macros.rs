@@ -1,3 +1,4 @@
+use crate::drivers::fbcon::{CONSOLE, get_console};
 use crate::drivers::serial::SERIAL; //not found in this scope
 use crate::drivers::serial::get_serial;
 use core::fmt::*;
@@ -61,14 +62,20 @@ pub fn print_internal(args: Arguments) {
         if let Some(mut serial) = get_serial() {
             serial.write_fmt(args).unwrap();
         }
+        if let Some(mut console) = get_console() {
+            console.write_fmt(args).unwrap();
+        }
     });
 }
 
 #[allow(dead_code)]
 #[cfg_attr(target_os = "none", panic_handler)]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    // force unlock serial for panic output
+    // force unlock serial and console for panic output
     unsafe { SERIAL.get().unwrap().force_unlock() };
+    if let Some(console) = CONSOLE.get() {
+        unsafe { console.force_unlock() };
+    }
 
     //llm assist
     println!("\n\r==================================================");
@@ -87,6 +94,8 @@ fn panic(info: &core::panic::PanicInfo) -> ! {
         );
     }
 
+    crate::utils::backtrace::print_panic();
+
     println!("--------------------------------------------------");
     println!("CPU halted. System needs to be restarted manually.");
     println!("==================================================\n\r");
@@ -3,8 +3,10 @@ mod macros;
 #[macro_use]
 mod regs;
 
+pub mod backtrace;
 pub mod func;
 pub mod logger;
+pub mod params;
 pub mod resource;
 
 pub use macros::*;
@@ -50,7 +52,7 @@ fn wait(pid: ProcessId) {
             })
         };
         if exit_code.is_none() {
-            x86_64::instructions::hlt();
+            crate::interrupt::clock::idle(None);
         } else {
             break;
         }
@@ -0,0 +1,167 @@
+//! Kernel parameters from the boot command line
+//!
+//! The command line is a list of `name=value` separated by spaces,
+//! a `name` alone sets a boolean parameter. Everything after `--` is
+//! passed to init as its arguments. Subsystems declare their parameters
+//! as `Param` statics, which are listed in `PARAMS`.
+
+use core::str::FromStr;
+
+use crate::drivers::{ata, power};
+use crate::interrupt::clock;
+use crate::memory::aslr;
+use crate::proc;
+use crate::utils::logger;
+
+/// All parameters accepted on the command line
+static PARAMS: &[&dyn Parameter] = &[
+    &logger::LOGLEVEL,
+    &logger::CONSOLE_LOGLEVEL,
+    &proc::init::INIT,
+    &proc::init::INIT_RESPAWN,
+    &proc::coredump::CORE_LIMIT,
+    &clock::HZ,
+    &clock::TIMER,
+    &aslr::ASLR,
+    &ata::ROOT,
+    &power::REBOOT,
+];
+
+static CMDLINE: spin::Once<&'static str> = spin::Once::new();
+
+static INIT_ARGS: spin::Once<&'static str> = spin::Once::new();
+
+/// Parse the command line, the last value of a parameter wins
+pub fn init(cmdline: &'static str) {
+    CMDLINE.call_once(|| cmdline);
+
+    let (params, init_args) = split_init_args(cmdline.trim());
+    INIT_ARGS.call_once(|| init_args);
+
+    // parameters are set once, so the command line is read backwards
+    for arg in params.split_whitespace().rev() {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, "1"));
+
+        match PARAMS.iter().find(|p| p.name() == name) {
+            Some(param) if param.is_set() => {
+                warn!("Kernel parameter {}={} is overridden", name, value)
+            }
+            Some(param) if param.set(value) => trace!("Kernel parameter: {}={}", name, value),
+            Some(_) => warn!("Invalid value of kernel parameter {}: {}", name, value),
+            None => warn!("Unknown kernel parameter: {}", name),
+        }
+    }
+
+    info!("Kernel command line: {}", cmdline);
+}
+
+/// The command line passed by the bootloader
+pub fn cmdline() -> &'static str {
+    CMDLINE.get().copied().unwrap_or_default()
+}
+
+/// Arguments of init after `--`
+pub fn init_args() -> &'static str {
+    INIT_ARGS.get().copied().unwrap_or_default()
+}
+
+/// Split the command line at the first `--`
+fn split_init_args(cmdline: &str) -> (&str, &str) {
+    if let Some(args) = cmdline.strip_prefix("--")
+        && (args.is_empty() || args.starts_with(' '))
+    {
+        return ("", args.trim());
+    }
+
+    match cmdline.split_once(" -- ") {
+        Some((params, args)) => (params, args.trim()),
+        None => (cmdline.strip_suffix(" --").unwrap_or(cmdline), ""),
+    }
+}
+
+/// A value which can be parsed from the command line
+pub trait ParamValue: Copy + Send + Sync + 'static {
+    fn parse(value: &'static str) -> Option<Self>;
+}
+
+impl ParamValue for &'static str {
+    fn parse(value: &'static str) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl ParamValue for bool {
+    fn parse(value: &'static str) -> Option<Self> {
+        match value {
+            "1" | "y" | "yes" | "on" | "true" => Some(true),
+            "0" | "n" | "no" | "off" | "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_param_value {
+    ($($t:ty),*) => {
+        $(
+            impl ParamValue for $t {
+                fn parse(value: &'static str) -> Option<Self> {
+                    match value.strip_prefix("0x") {
+                        Some(hex) => <$t>::from_str_radix(hex, 16).ok(),
+                        None => <$t>::from_str(value).ok(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_param_value!(u8, u16, u32, u64, usize);
+
+/// A typed kernel parameter with a default value
+pub struct Param<T: ParamValue> {
+    name: &'static str,
+    default: T,
+    value: spin::Once<T>,
+}
+
+impl<T: ParamValue> Param<T> {
+    pub const fn new(name: &'static str, default: T) -> Self {
+        Self {
+            name,
+            default,
+            value: spin::Once::new(),
+        }
+    }
+
+    /// The value from the command line, or the default
+    pub fn get(&self) -> T {
+        self.value.get().copied().unwrap_or(self.default)
+    }
+}
+
+/// Type erased parameter in `PARAMS`
+trait Parameter: Sync {
+    fn name(&self) -> &'static str;
+    fn is_set(&self) -> bool;
+    fn set(&self, value: &'static str) -> bool;
+}
+
+impl<T: ParamValue> Parameter for Param<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn is_set(&self) -> bool {
+        self.value.is_completed()
+    }
+
+    fn set(&self, value: &'static str) -> bool {
+        match T::parse(value) {
+            Some(value) => {
+                self.value.call_once(|| value);
+                true
+            }
+            None => false,
+        }
+    }
+}
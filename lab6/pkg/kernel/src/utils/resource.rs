@@ -10,30 +10,21 @@ pub enum StdIO {
     Stderr,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ResourceSet {
     pub handles: BTreeMap<u8, Mutex<Resource>>,
 }
 
-impl Default for ResourceSet {
-    fn default() -> Self {
-        let mut res = Self {
-            handles: BTreeMap::new(),
-        };
-
-        res.open(Resource::Console(StdIO::Stdin));
-        res.open(Resource::Console(StdIO::Stdout));
-        res.open(Resource::Console(StdIO::Stderr));
-
-        res
-    }
-}
-
 impl ResourceSet {
-    pub fn open(&mut self, res: Resource) -> u8 {
-        let fd = self.handles.len() as u8;
+    /// Open a resource with the lowest free fd, fail if `limit` fds are open
+    pub fn open(&mut self, res: Resource, limit: usize) -> Option<u8> {
+        if self.handles.len() >= limit {
+            return None;
+        }
+
+        let fd = (0..=u8::MAX).find(|fd| !self.handles.contains_key(fd))?;
         self.handles.insert(fd, Mutex::new(res));
-        fd
+        Some(fd)
     }
 
     pub fn close(&mut self, fd: u8) -> bool {
@@ -1,4 +1,10 @@
-use syscall_def::Syscall;
+pub use syscall_def::Syscall;
+pub use syscall_def::info::{AppInfo, ProcessInfo, ProcessStatus, ProcessTimes};
+pub use syscall_def::limit::{RLIM_INFINITY, Rlimit};
+pub use syscall_def::power::RebootMode;
+pub use syscall_def::signal::Signal;
+pub use syscall_def::syslog::{LEVEL_NAMES, SyslogAction};
+pub use syscall_def::trace::TraceRecord;
 
 #[inline(always)]
 pub fn sys_write(fd: u8, buf: &[u8]) -> Option<usize> {
@@ -45,6 +51,30 @@ pub fn sys_stat() {
     syscall!(Syscall::Stat);
 }
 
+/// Fill `buf` with the alive processes, return the count of them
+///
+/// The count may exceed `buf.len()`, pass an empty slice to query it.
+/// Fill `buf` with the arguments of the process, return their length
+///
+/// The length may exceed `buf.len()`, pass an empty slice to query it.
+#[inline(always)]
+pub fn sys_get_args(buf: &mut [u8]) -> usize {
+    syscall!(Syscall::GetArgs, buf.as_mut_ptr(), buf.len())
+}
+
+#[inline(always)]
+pub fn sys_list_process(buf: &mut [ProcessInfo]) -> usize {
+    syscall!(Syscall::ListProcess, buf.as_mut_ptr(), buf.len())
+}
+
+/// Fill `buf` with the loaded apps, return the count of them
+///
+/// The count may exceed `buf.len()`, pass an empty slice to query it.
+#[inline(always)]
+pub fn sys_list_app_info(buf: &mut [AppInfo]) -> usize {
+    syscall!(Syscall::ListAppInfo, buf.as_mut_ptr(), buf.len())
+}
+
 #[inline(always)]
 pub fn sys_allocate(layout: &core::alloc::Layout) -> *mut u8 {
     syscall!(Syscall::Allocate, layout as *const _) as *mut u8
@@ -60,6 +90,19 @@ pub fn sys_spawn(path: &str) -> u16 {
     syscall!(Syscall::Spawn, path.as_ptr() as u64, path.len() as u64) as u16
 }
 
+#[inline(always)]
+pub fn sys_chdir(path: &str) -> bool {
+    syscall!(Syscall::Chdir, path.as_ptr() as u64, path.len() as u64) == 0
+}
+
+/// Get the working directory, return the length of it
+///
+/// The length may exceed `buf.len()`, in which case the path is truncated.
+#[inline(always)]
+pub fn sys_getcwd(buf: &mut [u8]) -> usize {
+    syscall!(Syscall::Getcwd, buf.as_mut_ptr(), buf.len())
+}
+
 #[inline(always)]
 pub fn sys_get_pid() -> u16 {
     syscall!(Syscall::GetPid) as u16
@@ -70,10 +113,33 @@ pub fn sys_exit(code: isize) -> ! {
     syscall!(Syscall::Exit, code as u64);
     unreachable!("This process should be terminated by now.")
 }
+/// Return 0 in the child, the pid of child in the parent, or `u16::MAX` on failure
 #[inline(always)]
 pub fn sys_fork() -> u16 {
     syscall!(Syscall::Fork) as u16
 }
+/// Whether the kernel asked this process to terminate, e.g. on shutdown
+///
+/// The process is killed if it does not exit in time.
+#[inline(always)]
+pub fn sys_terminating() -> bool {
+    syscall!(Syscall::Terminating) != 0
+}
+/// Get the CPU times of the process, 0 for the current process
+#[inline(always)]
+pub fn sys_times(pid: u16) -> Option<ProcessTimes> {
+    let mut times = ProcessTimes::default();
+    let ret = syscall!(Syscall::Times, pid as u64, &mut times as *mut ProcessTimes);
+    (ret == 0).then_some(times)
+}
+#[inline(always)]
+pub fn sys_get_rlimit(res: Rlimit) -> usize {
+    syscall!(Syscall::GetRlimit, res as usize)
+}
+#[inline(always)]
+pub fn sys_set_rlimit(res: Rlimit, limit: usize) -> bool {
+    syscall!(Syscall::SetRlimit, res as usize, limit) == 0
+}
 #[inline(always)]
 pub fn sys_new_sem(key: u32, value: usize) -> bool {
     syscall!(Syscall::Sem, 0, key as usize, value) == 0
@@ -90,3 +156,51 @@ pub fn sys_sem_signal(key: u32) -> usize {
 pub fn sys_sem_remove(key: u32) -> usize {
     syscall!(Syscall::Sem, 1, key as usize)
 }
+#[inline(always)]
+pub fn sys_trace(pid: u16, enable: bool) -> bool {
+    syscall!(Syscall::Trace, 0, pid as usize, enable as usize) == 0
+}
+#[inline(always)]
+pub fn sys_read_trace(buf: &mut [TraceRecord]) -> usize {
+    syscall!(Syscall::Trace, 1, buf.as_mut_ptr(), buf.len())
+}
+#[inline(always)]
+pub fn sys_syslog_size() -> usize {
+    syscall!(Syscall::Syslog, SyslogAction::Size as usize)
+}
+#[inline(always)]
+pub fn sys_syslog_read(buf: &mut [u8], clear: bool) -> Option<usize> {
+    let action = if clear {
+        SyslogAction::ReadClear
+    } else {
+        SyslogAction::Read
+    };
+    let ret = syscall!(
+        Syscall::Syslog,
+        action as usize,
+        buf.as_mut_ptr(),
+        buf.len()
+    );
+    (ret != usize::MAX).then_some(ret)
+}
+#[inline(always)]
+pub fn sys_syslog_clear() -> bool {
+    syscall!(Syscall::Syslog, SyslogAction::Clear as usize) == 0
+}
+#[inline(always)]
+pub fn sys_syslog_console_level(level: usize) -> bool {
+    syscall!(Syscall::Syslog, SyslogAction::ConsoleLevel as usize, level) == 0
+}
+#[inline(always)]
+pub fn sys_syslog_level(spec: &str) -> bool {
+    syscall!(
+        Syscall::Syslog,
+        SyslogAction::Level as usize,
+        spec.as_ptr(),
+        spec.len()
+    ) == 0
+}
+#[inline(always)]
+pub fn sys_reboot(mode: RebootMode) -> bool {
+    syscall!(Syscall::Reboot, mode as usize) == 0
+}
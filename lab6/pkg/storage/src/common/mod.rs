@@ -9,6 +9,7 @@ mod filesystem;
 mod io;
 mod metadata;
 mod mount;
+mod path;
 
 use super::*;
 
@@ -20,5 +21,6 @@ pub use filesystem::*;
 pub use io::*;
 pub use metadata::*;
 pub use mount::*;
+pub use path::*;
 
 pub const PATH_SEPARATOR: char = '/';
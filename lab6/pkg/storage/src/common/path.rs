@@ -0,0 +1,56 @@
+use super::*;
+
+/// Resolve `path` against the absolute directory `cwd`
+///
+/// The result is an absolute path without empty or `.` components,
+/// `..` removes the previous component and stays at the root.
+pub fn resolve_path(cwd: &str, path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+
+    if !path.starts_with(PATH_SEPARATOR) {
+        components.extend(
+            cwd.split(PATH_SEPARATOR)
+                .filter(|c| !c.is_empty() && *c != "."),
+        );
+    }
+
+    for component in path.split(PATH_SEPARATOR) {
+        match component {
+            "" | "." => continue,
+            ".." => {
+                components.pop();
+            }
+            c => components.push(c),
+        }
+    }
+
+    let mut resolved = String::with_capacity(path.len() + cwd.len() + 1);
+    for component in components {
+        resolved.push(PATH_SEPARATOR);
+        resolved.push_str(component);
+    }
+
+    if resolved.is_empty() {
+        resolved.push(PATH_SEPARATOR);
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(resolve_path("/", "app"), "/app");
+        assert_eq!(resolve_path("/app", "hello"), "/app/hello");
+        assert_eq!(resolve_path("/app", "/hello"), "/hello");
+        assert_eq!(resolve_path("/app/", "./hello/"), "/app/hello");
+        assert_eq!(resolve_path("/a/b", "../c"), "/a/c");
+        assert_eq!(resolve_path("/a/b", "../../.."), "/");
+        assert_eq!(resolve_path("/a//./b", ".."), "/a");
+        assert_eq!(resolve_path("/", ""), "/");
+        assert_eq!(resolve_path("/a/b", "/x/../y/./z"), "/y/z");
+    }
+}
@@ -0,0 +1,102 @@
+use num_enum::FromPrimitive;
+
+pub const INFO_NAME_LEN: usize = 16;
+
+/// Status of a process, mirrors the kernel's `ProgramStatus`
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromPrimitive)]
+pub enum ProcessStatus {
+    Running = 0,
+    Ready = 1,
+    Blocked = 2,
+    Dead = 3,
+
+    #[default]
+    Unknown = 255,
+}
+
+/// A process record filled by `Syscall::ListProcess`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessInfo {
+    pub pid: u16,
+    /// 0 if the process has no parent
+    pub ppid: u16,
+    pub status: ProcessStatus,
+    /// NUL padded process name, truncated to `INFO_NAME_LEN` bytes
+    pub name: [u8; INFO_NAME_LEN],
+    pub ticks: usize,
+    /// Memory used by the process in bytes
+    pub memory: usize,
+    pub times: ProcessTimes,
+}
+
+/// CPU times of a process in TSC cycles, filled by `Syscall::Times`
+///
+/// Times of children are accumulated when they exit.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessTimes {
+    /// Cycles spent in user mode
+    pub user: u64,
+    /// Cycles spent in the kernel on behalf of the process
+    pub system: u64,
+    pub children_user: u64,
+    pub children_system: u64,
+    /// TSC when the process was created
+    pub start: u64,
+    /// TSC when the times were taken
+    pub now: u64,
+}
+
+/// An app record filled by `Syscall::ListAppInfo`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AppInfo {
+    /// NUL padded app name, truncated to `INFO_NAME_LEN` bytes
+    pub name: [u8; INFO_NAME_LEN],
+    /// Size of the ELF file in bytes
+    pub size: usize,
+    /// Entry point of the ELF file
+    pub entry: usize,
+}
+
+impl ProcessInfo {
+    pub fn name(&self) -> &str {
+        name_str(&self.name)
+    }
+}
+
+impl ProcessTimes {
+    /// Cycles the process has been running for
+    pub fn cpu(&self) -> u64 {
+        self.user + self.system
+    }
+
+    /// Cycles since the process was created
+    pub fn elapsed(&self) -> u64 {
+        self.now.saturating_sub(self.start)
+    }
+}
+
+impl AppInfo {
+    pub fn name(&self) -> &str {
+        name_str(&self.name)
+    }
+}
+
+/// Copy `name` into a NUL padded buffer, truncated at a char boundary
+pub fn name_buf(name: &str) -> [u8; INFO_NAME_LEN] {
+    let mut buf = [0u8; INFO_NAME_LEN];
+    let mut len = name.len().min(INFO_NAME_LEN);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+    buf
+}
+
+fn name_str(buf: &[u8; INFO_NAME_LEN]) -> &str {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(INFO_NAME_LEN);
+    core::str::from_utf8(&buf[..len]).unwrap_or("?")
+}
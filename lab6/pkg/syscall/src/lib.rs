@@ -2,7 +2,13 @@
 
 use num_enum::FromPrimitive;
 
+pub mod info;
+pub mod limit;
 pub mod macros;
+pub mod power;
+pub mod signal;
+pub mod syslog;
+pub mod trace;
 
 #[repr(usize)]
 #[derive(Clone, Debug, FromPrimitive)]
@@ -16,7 +22,19 @@ pub enum Syscall {
     Exit = 60,
     WaitPid = 61,
     Sem = 66,
+    Getcwd = 79,
+    Chdir = 80,
+    GetRlimit = 97,
+    Times = 100,
+    Trace = 101,
+    Syslog = 103,
+    SetRlimit = 160,
+    Reboot = 169,
 
+    Terminating = 65527,
+    GetArgs = 65528,
+    ListProcess = 65529,
+    ListAppInfo = 65530,
     ListApp = 65531,
     Stat = 65532,
     Allocate = 65533,
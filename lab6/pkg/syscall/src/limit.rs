@@ -0,0 +1,24 @@
+use num_enum::TryFromPrimitive;
+
+/// Value of an unlimited resource
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+pub const RLIMIT_COUNT: usize = 6;
+
+/// Resources limited per process
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+pub enum Rlimit {
+    /// Maximum size of the stack in bytes
+    Stack = 0,
+    /// Maximum size of heap allocations in bytes
+    Heap = 1,
+    /// Maximum count of open file descriptors
+    Files = 2,
+    /// Maximum count of alive children
+    Children = 3,
+    /// Maximum clock ticks the process may run
+    Cpu = 4,
+    /// Maximum size of a core dump in bytes, 0 disables core dumps
+    Core = 5,
+}
@@ -0,0 +1,13 @@
+use num_enum::TryFromPrimitive;
+
+/// Modes of `Syscall::Reboot`
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+pub enum RebootMode {
+    /// Turn the machine off
+    PowerOff = 0,
+    /// Restart the machine
+    Reboot = 1,
+    /// Stop the CPU, without resetting the machine
+    Halt = 2,
+}
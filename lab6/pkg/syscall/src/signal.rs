@@ -0,0 +1,42 @@
+/// Signals raised by the kernel
+///
+/// A process killed by a signal exits with the negated signal number.
+#[repr(isize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// Illegal instruction
+    Ill = 4,
+    /// Breakpoint or single step trap
+    Trap = 5,
+    /// Arithmetic error
+    Fpe = 8,
+    /// Killed by the kernel
+    Kill = 9,
+    /// Invalid memory access
+    Segv = 11,
+    /// Terminated on request, e.g. on shutdown
+    Term = 15,
+    /// CPU time limit exceeded
+    XCpu = 24,
+}
+
+impl Signal {
+    /// The exit code of a process killed by this signal
+    pub const fn exit_code(self) -> isize {
+        -(self as isize)
+    }
+
+    /// Get the signal from the exit code of a process
+    pub fn from_exit_code(code: isize) -> Option<Self> {
+        match -code {
+            4 => Some(Self::Ill),
+            5 => Some(Self::Trap),
+            8 => Some(Self::Fpe),
+            9 => Some(Self::Kill),
+            11 => Some(Self::Segv),
+            15 => Some(Self::Term),
+            24 => Some(Self::XCpu),
+            _ => None,
+        }
+    }
+}
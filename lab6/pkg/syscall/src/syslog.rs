@@ -0,0 +1,26 @@
+use num_enum::TryFromPrimitive;
+
+/// Actions of `Syscall::Syslog`
+///
+/// only `Read` and `Size` are allowed to processes other than init and
+/// its descendants, `ReadClear` then reads without clearing
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, TryFromPrimitive)]
+pub enum SyslogAction {
+    /// Copy the oldest records into a buffer
+    Read = 0,
+    /// Copy the oldest records into a buffer, then clear the log
+    ReadClear = 1,
+    /// Clear the log
+    Clear = 2,
+    /// Get the size of the log in bytes
+    Size = 3,
+    /// Set the level of records printed to the console
+    ConsoleLevel = 4,
+    /// Set the level of records kept in the log,
+    /// with a `level` or `module=level` spec
+    Level = 5,
+}
+
+/// Levels in the order of `log::LevelFilter`
+pub const LEVEL_NAMES: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
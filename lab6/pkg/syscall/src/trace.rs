@@ -0,0 +1,18 @@
+/// A syscall record produced by the kernel for a traced process
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceRecord {
+    /// The pid of the process which made the syscall
+    pub pid: u16,
+    /// Whether the syscall returned to the caller,
+    /// `false` if the process was blocked or exited in the syscall
+    pub returned: bool,
+    /// The syscall number
+    pub syscall: usize,
+    /// The raw syscall arguments
+    pub args: [usize; 3],
+    /// The value of rax after the syscall
+    pub ret: usize,
+    /// The clock tick when the syscall was made
+    pub tick: u64,
+}
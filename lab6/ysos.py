@@ -1,5 +1,6 @@
 #!/usr/bin/env python3
 
+import hashlib
 import os
 import shutil
 import subprocess
@@ -25,9 +26,17 @@ parser.add_argument('--bios', type=str,
 parser.add_argument('--boot', type=str, default='esp', help='Set boot path')
 parser.add_argument('--debug-listen', type=str, default='0.0.0.0:12345',
                     help='Set listen address for gdbserver')
+parser.add_argument('--initrd', type=str,
+                    help='Copy this disk image to the ESP as INITRD.IMG')
+parser.add_argument('--stub-listen', type=str,
+                    help='Connect COM2 to this listen address for the kernel GDB stub')
+parser.add_argument('--log', type=str, default='serial.log',
+                    help='Set serial log written by qemu, core dumps are extracted from it')
+parser.add_argument('--core-dir', type=str, default='core',
+                    help='Set output directory for extracted core dumps')
 
 parser.add_argument('task', type=str, choices=[
-                    'build', 'clean', 'launch', 'run', 'clippy'
+                    'build', 'clean', 'launch', 'run', 'clippy', 'cores'
                     ], default='build', help='Task to execute')
 
 args = parser.parse_args()
@@ -87,6 +96,15 @@ def qemu(output: str = '-nographic', memory: str = '96M', debug: bool = False, i
     qemu_args = [qemu_exe, '-bios', args.bios, '-net', 'none', *output.split(),
                  '-m', memory, '-drive', 'format=raw,file=fat:esp', '-snapshot']
 
+    if '-serial' not in output.split():
+        # COM1 keeps the console with the monitor, and is logged for `cores`
+        qemu_args += ['-chardev', f'stdio,id=com1,mux=on,logfile={args.log}',
+                      '-serial', 'chardev:com1', '-mon', 'chardev=com1']
+
+    if args.stub_listen:
+        # COM2 is served by the kernel GDB stub
+        qemu_args += ['-serial', f'tcp:{args.stub_listen},server,nowait']
+
     if debug:
         qemu_args += ['-gdb', f'tcp:{args.debug_listen}', '-S']
     elif intdbg:
@@ -135,6 +153,10 @@ def build():
     if os.path.exists(config_path):
         copy_to_esp(config_path, os.path.join('EFI', 'BOOT', 'boot.conf'))
 
+    # copy initial ramdisk, loaded if `initrd` is set in boot.conf
+    if args.initrd:
+        copy_to_esp(args.initrd, 'INITRD.IMG')
+
     # build kernel
     kernel = os.path.join(os.getcwd(), 'pkg', 'kernel')
     info('Building', 'kernel...')
@@ -163,6 +185,28 @@ def build():
             os.getcwd(), 'target', 'x86_64-unknown-ysos', profile_dir, app_name)
         copy_to_esp(compile_output, os.path.join('APP', app))
 
+    images = ['KERNEL.ELF'] + [f'APP/{app}' for app in apps]
+    if args.initrd:
+        images.append('INITRD.IMG')
+    write_checksums(images)
+
+
+def write_checksums(files: list):
+    # verified by the bootloader if `checksums` is set in boot.conf
+    dst = os.path.join(os.getcwd(), args.boot, 'SHA256SUMS')
+
+    if args.dry_run:
+        debug('Would write', dst)
+        return
+
+    with open(dst, 'w') as f:
+        for name in files:
+            with open(os.path.join(os.getcwd(), args.boot, name), 'rb') as image:
+                digest = hashlib.sha256(image.read()).hexdigest()
+            f.write(f'{digest}  {name}\n')
+
+    debug('Writing', dst)
+
 
 def clippy():
     cargo_exe = shutil.which('cargo')
@@ -184,6 +228,31 @@ def clippy():
         execute_command([cargo_exe, 'clippy'], app_path)
 
 
+def cores():
+    if not os.path.isfile(args.log):
+        raise Exception(f'{args.log} is not a file')
+
+    path = None
+    data = bytearray()
+
+    with open(args.log, 'r', errors='ignore') as f:
+        for line in f:
+            line = line.strip()
+
+            if line.startswith('CORE BEGIN '):
+                path = line.split()[2]
+                data = bytearray()
+            elif line == 'CORE END' and path is not None:
+                dst = os.path.join(args.core_dir, os.path.basename(path))
+                os.makedirs(args.core_dir, exist_ok=True)
+                with open(dst, 'wb') as core:
+                    core.write(data)
+                info('Extracted', f'{path} -> {dst} ({len(data)} bytes)')
+                path = None
+            elif path is not None:
+                data += bytes.fromhex(line)
+
+
 def clean():
     if os.path.exists(args.boot):
         shutil.rmtree(args.boot)
@@ -208,6 +277,8 @@ def main():
         qemu(args.output, args.memory, args.debug, args.intdbg)
     elif args.task == 'clippy':
         clippy()
+    elif args.task == 'cores':
+        cores()
 
 
 if __name__ == "__main__":